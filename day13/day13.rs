@@ -1,328 +1,323 @@
-use std::fs;
-use std::collections::VecDeque;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::{thread, time};
 use std::env;
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Opcode {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    AdjustRelativeBase,
-    Return,
-    Unknown,
-}
+#[path = "../intcode/vm.rs"]
+mod vm;
+#[path = "../intcode/loader.rs"]
+mod loader;
+#[path = "../intcode/session.rs"]
+mod session;
+#[path = "../intcode/render.rs"]
+mod render;
+#[path = "../intcode/cast.rs"]
+mod cast;
+#[allow(dead_code)]
+#[path = "../intcode/json.rs"]
+mod json;
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Status {
-    Running,
-    Finished,
-    Blocked,
-    Killed,
-}
+use vm::{Intcode, Snapshot};
+use session::Session;
+use render::{Renderer, Theme};
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative,
-    Unknown,
-}
+// Milliseconds between frames, unless given on the command line.
+const FRAME_DELAY:u64 = 33;
+// Frames drawn a second at most, however fast the game runs.
+const MAX_FPS:u32 = 60;
+// The shortest frame delay when playing, one frame at the highest rate, so
+// that speeding up never turns into a busy loop.
+const MIN_DELAY:u64 = 1000 / MAX_FPS as u64;
+// Frames kept for rewinding, and how many one rewind goes back.
+const MAX_SNAPSHOTS:usize = 3000;
+const REWIND_FRAMES:usize = 30;
+// Two quarters in memory address 0 to play for free.
+const FREE_PLAY:[(usize, i64); 1] = [(0, 2)];
 
-#[derive(Clone, Debug)]
-struct Intcode {
-    status: Status,
-    memory: HashMap<usize, i64>,
-    program_counter: usize,
-    relative_base: i64,
-    inputs: VecDeque<i64>,
-    outputs: VecDeque<i64>,
+// What the cabinet has drawn so far. The ball and the paddle are kept apart
+// from the other tiles.
+#[derive(Clone, Default)]
+struct Screen {
+    tiles: HashMap<(i32, i32), u8>,
+    ball: (i32, i32),
+    paddle: (i32, i32),
+    ball_direction: i32,
+    score: i64,
+    x_max: i32,
+    y_max: i32,
 }
 
-impl Intcode {
-    fn new(program:Vec<i64>) -> Intcode {
-        let mut m:HashMap<usize, i64> = HashMap::new();
-        for (i, &v) in program.iter().enumerate() {
-            m.insert(i, v);
-        }
-        Intcode {
-            status: Status::Running,
-            memory: m,
-            program_counter: 0,
-            relative_base: 0,
-            inputs: VecDeque::new(),
-            outputs: VecDeque::new(),
-        }
-    }
-
-    fn input(&mut self, value:i64) {
-        self.inputs.push_back(value);
-    }
+impl Screen {
+    // Draws every complete (x, y, tile) triple the cabinet has output.
+    fn update(&mut self, arcade_cabinet:&mut Intcode) {
+        while arcade_cabinet.outputs.len() >= 3 {
+            let x = arcade_cabinet.outputs.pop_front().unwrap() as i32;
+            let y = arcade_cabinet.outputs.pop_front().unwrap() as i32;
+            let t = arcade_cabinet.outputs.pop_front().unwrap();
 
-    fn execute(&mut self) {
-        while self.status == Status::Running {
-            self.tick();
+            if x == -1 && y == 0 {
+                self.score = t;
+            } else if t == 4 {
+                if self.ball != (0, 0) {
+                    self.ball_direction = x - self.ball.0;
+                }
+                self.ball = (x, y);
+            } else if t == 3 {
+                self.paddle = (x, y);
+            } else {
+                *self.tiles.entry((x, y)).or_insert(0) = t as u8;
+                self.x_max = self.x_max.max(x);
+                self.y_max = self.y_max.max(y);
+            }
         }
     }
 
-    fn tick(&mut self) {
-        let instruction = self.memory[&self.program_counter];
-        let opcode = self.opcode(instruction);
-        match opcode {
-            Opcode::Add => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a + b);
-                self.program_counter += 4;
-            },
-            Opcode::Multiply => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a * b);
-                self.program_counter += 4;
-            },
-            Opcode::Input => {
-                if let Some(input) = self.inputs.pop_front() {
-                    self.store_position(1, input);
-                    self.program_counter += 2;
-                } else {
-                    self.status = Status::Blocked;
-                }
-            },
-            Opcode::Output => {
-                let output = self.get_parameter(1);
-                self.outputs.push_back(output);
-                self.program_counter += 2;
-            },
-            Opcode::JumpIfTrue => {
-                let condition = self.get_parameter(1);
-                if condition != 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::JumpIfFalse => {
-                let condition = self.get_parameter(1);
-                if condition == 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::LessThan => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a < b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::Equals => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a == b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::AdjustRelativeBase => {
-                let a = self.get_parameter(1);
-                self.relative_base += a;
-                self.program_counter += 2;
-            },
-            Opcode::Return => {
-                self.status = Status::Finished;
-            },
-            Opcode::Unknown => {
-                self.status = Status::Killed;
-            },
-        }
+    fn frame(&self) -> Vec<Vec<u8>> {
+        (0..self.y_max + 1).map(|y| (0..self.x_max + 1).map(|x| {
+            if (x, y) == self.ball {
+                4
+            } else if (x, y) == self.paddle {
+                3
+            } else {
+                *self.tiles.get(&(x, y)).unwrap_or(&0)
+            }
+        }).collect()).collect()
     }
 
-    fn opcode(&mut self, instruction:i64) -> Opcode{
-        match instruction % 100 {
-            1 => Opcode::Add,
-            2 => Opcode::Multiply,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::JumpIfTrue,
-            6 => Opcode::JumpIfFalse,
-            7 => Opcode::LessThan,
-            8 => Opcode::Equals,
-            9 => Opcode::AdjustRelativeBase,
-            99 => Opcode::Return,
-            _ => Opcode::Unknown,
-        }
+    fn status(&self, text:&str) -> String {
+        format!("Score {:<8} Blocks {:<5} {}", self.score, self.blocks(), text)
     }
 
-    fn get_parameter(&mut self, offset:usize) -> i64 {
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => self.get_position(offset),
-            ParameterMode::Immediate => self.get(offset),
-            ParameterMode::Relative => self.get_relative(offset),
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
+    fn blocks(&self) -> usize {
+        self.tiles.values().filter(|&&t| t == 2).count()
     }
 
-    fn store_position(&mut self, offset:usize, value:i64) {
-        let store_index:usize;
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Immediate => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Relative => store_index = (self.relative_base + self.memory[&(self.program_counter + offset)]) as usize,
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
+    // The joystick position that keeps the paddle under the ball.
+    fn autopilot(&self) -> i64 {
+        let (ball, paddle) = (self.ball, self.paddle);
+        if ball.0 == paddle.0 {
+            if paddle.1 - ball.1 == 2 {
+                0
+            } else {
+                self.ball_direction as i64
+            }
+        } else if ball.0 < paddle.0 {
+            -1
+        } else {
+            1
         }
-
-        *self.memory.entry(store_index).or_insert(0) = value
     }
+}
 
-    fn get(&mut self, offset:usize) -> i64 {
-        *self.memory.entry(self.program_counter + offset).or_insert(0)
-    }
+// Walls, blocks, the paddle and the ball, three columns wide.
+fn theme() -> Theme {
+    Theme::new(3).tile(1, "|||", Some(244))
+                 .tile(2, "███", Some(208))
+                 .tile(3, "‾‾‾", Some(45))
+                 .tile(4, " * ", Some(226))
+}
 
-    fn get_position(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset) as usize;
-        *self.memory.entry(v).or_insert(0)
-    }
+// Puts the terminal into raw mode for as long as it lives, so that keys
+// arrive as they are pressed and are not echoed.
+struct RawTerminal {
+    saved: String,
+}
 
-    fn get_relative(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset);
-        *self.memory.entry((self.relative_base + v) as usize).or_insert(0)
+impl RawTerminal {
+    fn new() -> RawTerminal {
+        let saved = stty(&["-g"]);
+        stty(&["raw", "-echo"]);
+        print!("{}[?25l", 27 as char);
+        RawTerminal { saved: saved.trim().to_string() }
     }
+}
 
-    fn get_parameter_mode(&self, offset:usize) -> ParameterMode {
-        match (self.memory[&self.program_counter] / (10i64.pow(offset as u32 + 1))) % 10 {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            2 => ParameterMode::Relative,
-            _ => ParameterMode::Unknown,
-        }
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+        println!("{}[?25h", 27 as char);
     }
+}
 
-    fn finished(&self) -> bool {
-        self.status == Status::Finished
-    }
+fn stty(args:&[&str]) -> String {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output().expect("Unable to run stty");
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
 
-    fn blocked(&self) -> bool {
-        self.status == Status::Blocked
-    }
+enum Key {
+    Left,
+    Neutral,
+    Right,
+    Pause,
+    Rewind,
+    Faster,
+    Slower,
+    Quit,
+}
 
-    fn r#continue(&mut self) {
-        if self.blocked() && self.inputs.len() > 0 {
-            self.status = Status::Running;
+// Reads keys on their own thread, so that the game goes on when none is
+// pressed. Arrow keys arrive as escape sequences ending in C or D.
+fn keyboard() -> mpsc::Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut byte = [0u8; 1];
+        while io::stdin().read(&mut byte).map(|n| n == 1).unwrap_or(false) {
+            let key = match byte[0] {
+                b'a' | b'h' | b'D' => Key::Left,
+                b's' | b'j' | b'B' | b' ' => Key::Neutral,
+                b'd' | b'l' | b'C' => Key::Right,
+                b'p' => Key::Pause,
+                b'r' => Key::Rewind,
+                b'+' | b'=' => Key::Faster,
+                b'-' => Key::Slower,
+                b'q' | 3 => Key::Quit,
+                _ => continue,
+            };
+            if sender.send(key).is_err() {
+                break;
+            }
         }
-        self.execute();
-    }
+    });
+    receiver
 }
 
-fn print_game_state(x_max:i32, y_max:i32, game:&HashMap<(i32, i32), u8>, ball:&(i32, i32), paddle:&(i32, i32)) {
-    print!("{}[2J", 27 as char);
-    for y in 0..y_max + 1 {
-        let mut row:Vec<u8> = Vec::new();
-        for x in 0..x_max + 1 {
-            if &(x, y) == ball {
-                row.push(4);
-            } else if &(x, y) == paddle {
-                row.push(3);
-            } else {
-                if let Some(panel) = game.get(&(x, y)) {
-                    row.push(*panel);
-                } else {
-                    row.push(0);
-                }
+// Lets the player steer the paddle: a, d or the arrow keys move it for a
+// frame, p pauses, r rewinds, + and - change the speed and q quits. Returns
+// the final score.
+fn play(program:&Vec<i64>, mut delay:u64, mut renderer:Renderer) -> i64 {
+    let mut arcade_cabinet = Intcode::new(program.clone());
+    let mut screen = Screen::default();
+    let mut history:VecDeque<(Snapshot, Screen)> = VecDeque::new();
+    let mut paused = false;
+    let terminal = RawTerminal::new();
+    let keys = keyboard();
+    renderer.set_max_fps(MAX_FPS);
+    delay = delay.max(MIN_DELAY);
+
+    arcade_cabinet.execute();
+    screen.update(&mut arcade_cabinet);
+    while !arcade_cabinet.finished() {
+        let status = if paused { "PAUSED".to_string() } else { format!("{} ms per frame", delay) };
+        renderer.draw(screen.frame(), &screen.status(&status));
+        // Without a keyboard there is nobody left to play.
+        let key = if paused {
+            keys.recv().unwrap_or(Key::Quit)
+        } else {
+            match keys.recv_timeout(time::Duration::from_millis(delay)) {
+                Ok(key) => key,
+                Err(mpsc::RecvTimeoutError::Timeout) => Key::Neutral,
+                Err(mpsc::RecvTimeoutError::Disconnected) => Key::Quit,
             }
+        };
+        let joystick = match key {
+            Key::Left => -1,
+            Key::Right => 1,
+            Key::Neutral => 0,
+            Key::Pause => {
+                paused = !paused;
+                continue;
+            },
+            Key::Rewind => {
+                for _ in 1..REWIND_FRAMES.min(history.len()) {
+                    history.pop_back();
+                }
+                if let Some((snapshot, saved)) = history.pop_back() {
+                    arcade_cabinet.restore(&snapshot);
+                    screen = saved;
+                }
+                continue;
+            },
+            Key::Faster => {
+                delay = (delay * 2 / 3).max(MIN_DELAY);
+                continue;
+            },
+            Key::Slower => {
+                delay = (delay * 3 / 2).max(delay + 1);
+                continue;
+            },
+            Key::Quit => break,
+        };
+        if paused {
+            continue;
+        }
+        history.push_back((arcade_cabinet.snapshot(), screen.clone()));
+        if history.len() > MAX_SNAPSHOTS {
+            history.pop_front();
         }
-        let line:String = row.iter().map(|i| if i == &1 { "|||" } else if i == &2 { "███" } else if i == &3 { "‾‾‾" } else if i == &4 { " * " } else { "   " }).collect();
-        println!("{}", line);
+        arcade_cabinet.input(joystick);
+        arcade_cabinet.r#continue();
+        screen.update(&mut arcade_cabinet);
     }
-    thread::sleep(time::Duration::from_millis(33));
+    renderer.draw(screen.frame(), &screen.status(if screen.blocks() == 0 { "YOU WIN" } else { "GAME OVER" }));
+    renderer.finish();
+    drop(terminal);
+    screen.score
 }
 
+// Arguments: 1 [ms] to watch the paddle play itself, play [ms] to play,
+// record <file> and replay <file>. Watching and playing can be saved with
+// cast=<file> and interval=<ms>.
 fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let args: Vec<String> = env::args().collect();
-    let mut program: Vec<i64> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
-    let mut tiles:HashMap<(i32, i32), u8> = HashMap::new();
-    let mut paddle:(i32, i32) = (0, 0);
-    let mut ball:(i32, i32) = (0, 0);
+    let mut program: Vec<i64> = loader::load_or_exit("input.txt");
+    let mut args: Vec<String> = env::args().collect();
+    let cast = render::cast_options(&mut args);
+    let mut renderer = Renderer::new(theme());
+    if let Some((path, interval)) = &cast {
+        renderer.record(path, *interval);
+    }
+    let delay = args.get(2).and_then(|ms| ms.parse().ok()).unwrap_or(FRAME_DELAY);
 
     let mut arcade_cabinet = Intcode::new(program.clone());
     arcade_cabinet.execute();
     println!("There are {:?} block tiles are on the screen when the game exits", arcade_cabinet.outputs.iter().enumerate().fold(0, |acc, (i, x)| if (i + 1) % 3 == 0 && *x == 2 { acc + 1 } else { acc } ));
 
-    program[0] = 2;
-    arcade_cabinet = Intcode::new(program);
-    let mut x_max:i32 = 0;
-    let mut y_max:i32 = 0;
-    let mut ball_direction = 0;
-    let mut paddle_direction;
-    let mut score = 0;
-    let print = args.len() == 2 && &args[1] == "1";
+    let original = program.clone();
+    for &(address, value) in FREE_PLAY.iter() {
+        program[address] = value;
+    }
+    if args.len() == 3 && &args[1] == "replay" {
+        match Session::load(&args[2]).and_then(|session| session.replay(&original)) {
+            Ok(()) => println!("Replay of {} matches", args[2]),
+            Err(error) => {
+                println!("Replay of {} differs: {}", args[2], error);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    if args.len() > 1 && &args[1] == "play" {
+        println!("Your score is {:?}", play(&program, delay, renderer));
+        return;
+    }
+    let record = args.len() == 3 && &args[1] == "record";
+    arcade_cabinet = Intcode::new(program.clone());
+    arcade_cabinet.set_recording(record);
+    let mut screen = Screen::default();
+    let print = (args.len() > 1 && &args[1] == "1") || cast.is_some();
 
     while !arcade_cabinet.finished() {
         if arcade_cabinet.blocked() {
-            if ball.0 == paddle.0 {
-                if paddle.1 - ball.1 == 2 {
-                    paddle_direction = 0;
-                } else {
-                    paddle_direction = ball_direction;
-                }
-            } else if ball.0 < paddle.0 {
-                paddle_direction = -1;
-            } else {
-                paddle_direction = 1;
-            }
-            arcade_cabinet.input(paddle_direction as i64);
-
+            arcade_cabinet.input(screen.autopilot());
             arcade_cabinet.r#continue();
         } else {
             arcade_cabinet.execute();
         }
+        screen.update(&mut arcade_cabinet);
 
-        while arcade_cabinet.outputs.len() > 0 && arcade_cabinet.outputs.len() % 3 == 0 {
-            let x = arcade_cabinet.outputs.pop_front().unwrap() as i32;
-            let y = arcade_cabinet.outputs.pop_front().unwrap() as i32;
-            let t = arcade_cabinet.outputs.pop_front().unwrap();
-
-            if x == -1 && y == 0 {
-                score = t;
-            } else if t == 4 {
-                if ball != (0, 0) {
-                    ball_direction = x - ball.0;
-                }
-                ball = (x, y);
-            } else if t == 3 {
-                paddle = (x, y);
-            } else {
-                *tiles.entry((x, y)).or_insert(0) = t as u8;
-            }
-        }
-
-        if x_max == 0 {
-            let (x_coords, y_coords): (Vec<_>, Vec<_>) = tiles.keys().cloned().unzip();
-            x_max = *x_coords.iter().max().unwrap();
-            y_max = *y_coords.iter().max().unwrap();
-        }
-
-        if print {
-            print_game_state(x_max, y_max, &tiles, &ball, &paddle);
+        if print && renderer.draw(screen.frame(), &screen.status("")) {
+            thread::sleep(time::Duration::from_millis(delay));
         }
     }
+    if print {
+        renderer.finish();
+    }
 
-    println!("The score after the last block is broken is {:?}", score);
-}
\ No newline at end of file
+    println!("The score after the last block is broken is {:?}", screen.score);
+    if record {
+        let session = Session::from_run(&original, &FREE_PLAY, &arcade_cabinet);
+        session.save(&args[2]);
+        println!("Recorded {} inputs and {} outputs to {}", session.inputs.len(), session.outputs.len(), args[2]);
+    }
+}
@@ -1,233 +1,28 @@
-use std::fs;
 use std::u64;
 use std::env;
-use std::usize;
+
+#[path = "../intcode/vm.rs"]
+mod vm;
+#[path = "../intcode/loader.rs"]
+mod loader;
+#[path = "../intcode/render.rs"]
+mod render;
+#[path = "../intcode/image.rs"]
+mod image;
+#[path = "../intcode/cast.rs"]
+mod cast;
+#[allow(dead_code)]
+#[path = "../intcode/json.rs"]
+mod json;
+
+use vm::Intcode;
+use render::{Renderer, Theme};
+use image::{Image, Palette};
 // use std::cmp::Ordering;
-use std::collections::VecDeque;
 use std::collections::HashMap;
 use std::collections::HashSet;
 // use std::collections::BinaryHeap;
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Opcode {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    AdjustRelativeBase,
-    Return,
-    Unknown,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Status {
-    Running,
-    Finished,
-    Blocked,
-    Killed,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative,
-    Unknown,
-}
-
-#[derive(Clone, Debug)]
-struct Intcode {
-    status: Status,
-    memory: HashMap<usize, i64>,
-    program_counter: usize,
-    relative_base: i64,
-    inputs: VecDeque<i64>,
-    outputs: VecDeque<i64>,
-}
-
-impl Intcode {
-    fn new(program:Vec<i64>) -> Intcode {
-        let mut m:HashMap<usize, i64> = HashMap::new();
-        for (i, &v) in program.iter().enumerate() {
-            m.insert(i, v);
-        }
-        Intcode {
-            status: Status::Running,
-            memory: m,
-            program_counter: 0,
-            relative_base: 0,
-            inputs: VecDeque::new(),
-            outputs: VecDeque::new(),
-        }
-    }
-
-    fn input(&mut self, value:i64) {
-        self.inputs.push_back(value);
-    }
-
-    fn execute(&mut self) {
-        while self.status == Status::Running {
-            self.tick();
-        }
-    }
-
-    fn tick(&mut self) {
-        let instruction = self.memory[&self.program_counter];
-        let opcode = self.opcode(instruction);
-        match opcode {
-            Opcode::Add => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a + b);
-                self.program_counter += 4;
-            },
-            Opcode::Multiply => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a * b);
-                self.program_counter += 4;
-            },
-            Opcode::Input => {
-                if let Some(input) = self.inputs.pop_front() {
-                    self.store_position(1, input);
-                    self.program_counter += 2;
-                } else {
-                    self.status = Status::Blocked;
-                }
-            },
-            Opcode::Output => {
-                let output = self.get_parameter(1);
-                self.outputs.push_back(output);
-                self.program_counter += 2;
-            },
-            Opcode::JumpIfTrue => {
-                let condition = self.get_parameter(1);
-                if condition != 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::JumpIfFalse => {
-                let condition = self.get_parameter(1);
-                if condition == 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::LessThan => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a < b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::Equals => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a == b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::AdjustRelativeBase => {
-                let a = self.get_parameter(1);
-                self.relative_base += a;
-                self.program_counter += 2;
-            },
-            Opcode::Return => {
-                self.status = Status::Finished;
-            },
-            Opcode::Unknown => {
-                self.status = Status::Killed;
-            },
-        }
-    }
-
-    fn opcode(&mut self, instruction:i64) -> Opcode{
-        match instruction % 100 {
-            1 => Opcode::Add,
-            2 => Opcode::Multiply,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::JumpIfTrue,
-            6 => Opcode::JumpIfFalse,
-            7 => Opcode::LessThan,
-            8 => Opcode::Equals,
-            9 => Opcode::AdjustRelativeBase,
-            99 => Opcode::Return,
-            _ => Opcode::Unknown,
-        }
-    }
-
-    fn get_parameter(&mut self, offset:usize) -> i64 {
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => self.get_position(offset),
-            ParameterMode::Immediate => self.get(offset),
-            ParameterMode::Relative => self.get_relative(offset),
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-    }
-
-    fn store_position(&mut self, offset:usize, value:i64) {
-        let store_index:usize;
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Immediate => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Relative => store_index = (self.relative_base + self.memory[&(self.program_counter + offset)]) as usize,
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-
-        *self.memory.entry(store_index).or_insert(0) = value
-    }
-
-    fn get(&mut self, offset:usize) -> i64 {
-        *self.memory.entry(self.program_counter + offset).or_insert(0)
-    }
-
-    fn get_position(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset) as usize;
-        *self.memory.entry(v).or_insert(0)
-    }
-
-    fn get_relative(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset);
-        *self.memory.entry((self.relative_base + v) as usize).or_insert(0)
-    }
-
-    fn get_parameter_mode(&self, offset:usize) -> ParameterMode {
-        match (self.memory[&self.program_counter] / (10i64.pow(offset as u32 + 1))) % 10 {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            2 => ParameterMode::Relative,
-            _ => ParameterMode::Unknown,
-        }
-    }
-
-    fn blocked(&self) -> bool {
-        self.status == Status::Blocked
-    }
-
-    fn r#continue(&mut self) {
-        if self.blocked() && self.inputs.len() > 0 {
-            self.status = Status::Running;
-        }
-        self.execute();
-    }
-}
-
 type Coordinates = (i32, i32);
 type AreaMap = HashMap<Coordinates, u8>;
 
@@ -392,7 +187,7 @@ fn shortest_path(position:Coordinates, steps:u64, map:&AreaMap, visited:&mut Has
     min_steps
 }
 
-fn wall_follower(map:&mut AreaMap, start:Coordinates, robot:&mut Intcode) -> Coordinates {
+fn wall_follower(map:&mut AreaMap, start:Coordinates, robot:&mut Intcode, renderer:&mut Option<Renderer>) -> Coordinates {
     let mut goal = (-1, -1);
     let (mut x, mut y) = start;
 
@@ -437,6 +232,10 @@ fn wall_follower(map:&mut AreaMap, start:Coordinates, robot:&mut Intcode) -> Coo
             _ => unreachable!(),
         }
 
+        if let Some(renderer) = renderer.as_mut() {
+            renderer.draw(map_frame(map, &(x, y)), &format!("{} locations explored", map.len()));
+        }
+
         if (x, y) == start && direction == Direction::North {
             // Back at starting point & direction
             break;
@@ -448,46 +247,63 @@ fn wall_follower(map:&mut AreaMap, start:Coordinates, robot:&mut Intcode) -> Coo
     goal
 }
 
-fn print_map(map:&AreaMap, droid:&Coordinates) {
+// The explored map, framed by its bounds so far, with the droid and the
+// starting point drawn over it.
+fn map_frame(map:&AreaMap, droid:&Coordinates) -> Vec<Vec<u8>> {
     let (x_coords, y_coords): (Vec<_>, Vec<_>) = map.keys().cloned().unzip();
     let (&x_min, &x_max) = (x_coords.iter().min().unwrap(), x_coords.iter().max().unwrap());
     let (&y_min, &y_max) = (y_coords.iter().min().unwrap(), y_coords.iter().max().unwrap());
 
-    print!("{}[2J", 27 as char);
-    for y in y_min..y_max + 1 {
-        let mut row:Vec<u8> = Vec::new();
-        for x in x_min..x_max + 1 {
-            if &(x, y) == droid {
-                row.push(3);
-            } else if &(x, y) == &(0, 0) {
-                row.push(255);
-            } else {
-                if let Some(area) = map.get(&(x, y)) {
-                    row.push(*area);
-                } else {
-                    row.push(4);
-                }
-            }
+    (y_min..y_max + 1).map(|y| (x_min..x_max + 1).map(|x| {
+        if &(x, y) == droid {
+            3
+        } else if &(x, y) == &(0, 0) {
+            255
+        } else {
+            *map.get(&(x, y)).unwrap_or(&4)
         }
-        let line:String = row.iter().map(|i| if i == &0 { "#" } else if i == &1 { "." } else if i == &2 { "O" } else if i == &3 { "D" } else if i == &255 { "X" } else { " " }).collect();
-        println!("{}", line);
-    }
+    }).collect()).collect()
+}
+
+fn theme() -> Theme {
+    Theme::new(1).tile(0, "#", Some(240))
+                 .tile(1, ".", Some(250))
+                 .tile(2, "O", Some(39))
+                 .tile(3, "D", Some(226))
+                 .tile(255, "X", Some(196))
 }
 
 fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let args: Vec<String> = env::args().collect();
-    let print = args.len() == 2 && &args[1] == "1";
-    let program: Vec<i64> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
+    let program: Vec<i64> = loader::load_or_exit("input.txt");
+    let mut args: Vec<String> = env::args().collect();
+    let cast = render::cast_options(&mut args);
+    let export = image::image_options(&mut args);
+    let print = (args.len() == 2 && &args[1] == "1") || cast.is_some();
     let mut map = AreaMap::new();
     let goal:Coordinates;
 
-    goal = wall_follower(&mut map, (0, 0), &mut Intcode::new(program.clone()));
+    let mut renderer = if print { Some(Renderer::new(theme())) } else { None };
+    if let Some(renderer) = renderer.as_mut() {
+        renderer.set_max_fps(60);
+        if let Some((path, interval)) = &cast {
+            renderer.record(path, *interval);
+        }
+    }
+
+    goal = wall_follower(&mut map, (0, 0), &mut Intcode::new(program.clone()), &mut renderer);
+
+    if let Some(renderer) = renderer.as_mut() {
+        renderer.draw(map_frame(&map, &(255, 255)), &format!("{} locations explored", map.len()));
+        renderer.finish();
+    }
 
-    if print {
-        print_map(&map, &(255, 255));
+    if let Some((path, scale)) = &export {
+        let mut picture = map.clone();
+        picture.insert((0, 0), 255);
+        let palette = Palette::new([0, 0, 0]).color(0, [90, 90, 90]).color(1, [220, 220, 220])
+                                             .color(2, [30, 144, 255]).color(255, [220, 20, 60]);
+        Image::from_grid(&picture, &palette, *scale).save(path);
+        println!("Saved the map to {}", path);
     }
 
     let moves = shortest_path((0, 0), 0, &map, &mut HashSet::new());
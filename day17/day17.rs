@@ -1,233 +1,24 @@
-use std::fs;
 use std::u64;
 use std::env;
 use std::usize;
-use std::collections::VecDeque;
 use std::collections::HashMap;
 use std::char;
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Opcode {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    AdjustRelativeBase,
-    Return,
-    Unknown,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Status {
-    Running,
-    Finished,
-    Blocked,
-    Killed,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative,
-    Unknown,
-}
-
-#[derive(Clone, Debug)]
-struct Intcode {
-    status: Status,
-    memory: HashMap<usize, i64>,
-    program_counter: usize,
-    relative_base: i64,
-    inputs: VecDeque<i64>,
-    outputs: VecDeque<i64>,
-    live_feed: bool
-}
-
-impl Intcode {
-    fn new(program:Vec<i64>) -> Intcode {
-        let mut m:HashMap<usize, i64> = HashMap::new();
-        for (i, &v) in program.iter().enumerate() {
-            m.insert(i, v);
-        }
-        Intcode {
-            status: Status::Running,
-            memory: m,
-            program_counter: 0,
-            relative_base: 0,
-            inputs: VecDeque::new(),
-            outputs: VecDeque::new(),
-            live_feed: false
-        }
-    }
-
-    fn set_live_feed(&mut self, live:bool) {
-        self.live_feed = live;
-    }
-
-    fn append_input(&mut self, values:Vec<i64>) {
-        self.inputs.append(&mut VecDeque::from(values.clone()));
-    }
-
-    fn execute(&mut self) {
-        while self.status == Status::Running {
-            self.tick();
-        }
-    }
-
-    fn print_output(&mut self) {
-        print!("{}", self.outputs.iter().map(|&c| c as u8 as char).collect::<String>());
-        self.outputs.clear();
-    }
-
-    fn tick(&mut self) {
-        let instruction = self.memory[&self.program_counter];
-        let opcode = self.opcode(instruction);
-        match opcode {
-            Opcode::Add => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a + b);
-                self.program_counter += 4;
-            },
-            Opcode::Multiply => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a * b);
-                self.program_counter += 4;
-            },
-            Opcode::Input => {
-                if let Some(input) = self.inputs.pop_front() {
-                    self.store_position(1, input);
-                    self.program_counter += 2;
-                } else {
-                    self.status = Status::Blocked;
-                }
-            },
-            Opcode::Output => {
-                let output = self.get_parameter(1);
-                self.outputs.push_back(output);
-                self.program_counter += 2;
-                if self.live_feed && output == 10 {
-                    self.print_output();
-                }
-            },
-            Opcode::JumpIfTrue => {
-                let condition = self.get_parameter(1);
-                if condition != 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::JumpIfFalse => {
-                let condition = self.get_parameter(1);
-                if condition == 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::LessThan => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a < b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::Equals => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a == b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::AdjustRelativeBase => {
-                let a = self.get_parameter(1);
-                self.relative_base += a;
-                self.program_counter += 2;
-            },
-            Opcode::Return => {
-                self.status = Status::Finished;
-            },
-            Opcode::Unknown => {
-                self.status = Status::Killed;
-            },
-        }
-    }
-
-    fn opcode(&mut self, instruction:i64) -> Opcode{
-        match instruction % 100 {
-            1 => Opcode::Add,
-            2 => Opcode::Multiply,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::JumpIfTrue,
-            6 => Opcode::JumpIfFalse,
-            7 => Opcode::LessThan,
-            8 => Opcode::Equals,
-            9 => Opcode::AdjustRelativeBase,
-            99 => Opcode::Return,
-            _ => Opcode::Unknown,
-        }
-    }
-
-    fn get_parameter(&mut self, offset:usize) -> i64 {
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => self.get_position(offset),
-            ParameterMode::Immediate => self.get(offset),
-            ParameterMode::Relative => self.get_relative(offset),
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-    }
-
-    fn store_position(&mut self, offset:usize, value:i64) {
-        let store_index:usize;
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Immediate => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Relative => store_index = (self.relative_base + self.memory[&(self.program_counter + offset)]) as usize,
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-
-        *self.memory.entry(store_index).or_insert(0) = value
-    }
-
-    fn get(&mut self, offset:usize) -> i64 {
-        *self.memory.entry(self.program_counter + offset).or_insert(0)
-    }
-
-    fn get_position(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset) as usize;
-        *self.memory.entry(v).or_insert(0)
-    }
-
-    fn get_relative(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset);
-        *self.memory.entry((self.relative_base + v) as usize).or_insert(0)
-    }
-
-    fn get_parameter_mode(&self, offset:usize) -> ParameterMode {
-        match (self.memory[&self.program_counter] / (10i64.pow(offset as u32 + 1))) % 10 {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            2 => ParameterMode::Relative,
-            _ => ParameterMode::Unknown,
-        }
-    }
-}
+#[path = "../intcode/vm.rs"]
+mod vm;
+#[path = "../intcode/loader.rs"]
+mod loader;
+#[path = "../intcode/render.rs"]
+mod render;
+#[path = "../intcode/cast.rs"]
+mod cast;
+#[allow(dead_code)]
+#[path = "../intcode/json.rs"]
+mod json;
+
+use vm::Intcode;
+use vm::AsciiEcho;
+use render::{Renderer, Theme};
 
 type Coordinates = (i32, i32);
 type AreaMap = HashMap<Coordinates, u8>;
@@ -457,14 +248,32 @@ fn string_to_intcode_input(string:String) -> Vec<i64> {
     input.chars().map(|c| c as i64).collect::<Vec<_>>()
 }
 
+// The camera frames of the video feed: blocks of scaffold rows between
+// blank lines, without the prompts in between.
+fn video_frames(outputs:&Vec<i64>) -> Vec<Vec<Vec<u8>>> {
+    let text:String = outputs.iter().filter(|&&c| c < 128).map(|&c| c as u8 as char).collect();
+    text.split("\n\n")
+        .map(|block| block.lines().map(|line| line.bytes().collect::<Vec<u8>>()).collect::<Vec<_>>())
+        .filter(|rows| !rows.is_empty() && rows.iter().all(|row| !row.is_empty() && row.iter().all(|c| b"#.^v<>X".contains(c))))
+        .collect()
+}
+
+fn theme() -> Theme {
+    let robot = Some(226);
+    Theme::new(1).tile(b'#', "#", Some(250))
+                 .tile(b'.', ".", Some(237))
+                 .tile(b'^', "^", robot).tile(b'v', "v", robot).tile(b'<', "<", robot).tile(b'>', ">", robot)
+                 .tile(b'X', "X", Some(196))
+}
+
+// Arguments: 1 to print the map and 1 y to watch the video feed, which
+// cast=<file> and interval=<ms> save as an asciicast.
 fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let args: Vec<String> = env::args().collect();
+    let program: Vec<i64> = loader::load_or_exit("input.txt");
+    let mut args: Vec<String> = env::args().collect();
+    let cast = render::cast_options(&mut args);
     let print = args.len() == 2 && &args[1] == "1";
-    let video_feed = args.len() == 3 && &args[2] == "y";
-    let program: Vec<i64> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
+    let video_feed = (args.len() == 3 && &args[2] == "y") || cast.is_some();
     let mut map = AreaMap::new();
     let mut robot = Intcode::new(program.clone());
 
@@ -521,10 +330,22 @@ fn main() {
     let mut prog = program.clone();
     prog[0] = 2;
     robot = Intcode::new(prog);
-    robot.set_live_feed(video_feed);
+    if video_feed && cast.is_none() {
+        robot.add_observer(AsciiEcho);
+    }
     for i in robot_inputs {
-        robot.append_input(string_to_intcode_input(i));
+        robot.append_input(&string_to_intcode_input(i));
     }
     robot.execute();
+    if let Some((path, interval)) = &cast {
+        let frames = video_frames(&robot.outputs.iter().cloned().collect());
+        let mut renderer = Renderer::new(theme());
+        renderer.set_max_fps(30);
+        renderer.record(path, *interval);
+        for (i, frame) in frames.into_iter().enumerate() {
+            renderer.draw(frame, &format!("Frame {}", i + 1));
+        }
+        renderer.finish();
+    }
     println!("The vacuum robot has collected {:?} dust", robot.outputs.pop_back().expect("NaN"));
 }
@@ -5,6 +5,12 @@ use std::collections::VecDeque;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::BinaryHeap;
+use std::env;
+
+#[path = "../intcode/image.rs"]
+mod image;
+
+use image::{Image, Palette};
 
 type Coordinates = (i32, i32);
 type AreaMap = HashMap<Coordinates, char>;
@@ -136,8 +142,21 @@ fn shortest_distance(robots:&Items, routes:&RouteMap, key_map:&KeyMap) -> u64 {
     0
 }
 
+// Walls, open passages, the entrance, keys and doors.
+fn palette() -> Palette<char> {
+    let mut palette = Palette::new([0, 0, 0]).color('#', [90, 90, 90]).color('.', [220, 220, 220]).color('@', [220, 20, 60]);
+    for (key, door) in (b'a'..=b'z').zip(b'A'..=b'Z') {
+        palette = palette.color(key as char, [255, 215, 0]).color(door as char, [139, 69, 19]);
+    }
+    palette
+}
+
+// Arguments: image=<file> and scale=<pixels> to save the vault as a PNG or
+// PPM.
 fn main() {
     let data = fs::read_to_string("input.txt").expect("Unable to read file");
+    let mut args: Vec<String> = env::args().collect();
+    let export = image::image_options(&mut args);
     let lines: Vec<_> = data.split('\n').collect();
     let mut map:AreaMap = AreaMap::new();
     let mut keys:ItemLocations = ItemLocations::new();
@@ -171,6 +190,13 @@ fn main() {
 
     }
 
+    if let Some((path, scale)) = &export {
+        let mut picture = map.clone();
+        picture.insert(entrance, '@');
+        Image::from_grid(&picture, &palette(), *scale).save(path);
+        println!("Saved the vault to {}", path);
+    }
+
     let mut key_map = KeyMap::new();
     let mut sorted_keys = keys.keys().map(|&c| c).collect::<Items>();
     sorted_keys.sort();
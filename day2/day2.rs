@@ -1,6 +1,7 @@
-use std::fs;
+#[path = "../intcode/loader.rs"]
+mod loader;
 
-fn intcode(mut program:Vec<i32>, noun:i32, verb:i32) -> i32 {
+pub fn intcode(mut program:Vec<i32>, noun:i32, verb:i32) -> i32 {
     program[1] = noun;
     program[2] = verb;
 
@@ -22,10 +23,7 @@ fn intcode(mut program:Vec<i32>, noun:i32, verb:i32) -> i32 {
 }
 
 fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let program: Vec<i32> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
+    let program: Vec<i32> = loader::load_or_exit("input.txt").iter().map(|&v| v as i32).collect();
 
     println!("Value at position 0 after program halt is {}", intcode(program.clone(), 12, 2));
 
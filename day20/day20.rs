@@ -6,6 +6,11 @@ use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::collections::BinaryHeap;
 
+#[path = "../intcode/image.rs"]
+mod image;
+
+use image::{Image, Palette};
+
 type Coordinates = (i32, i32);
 type AreaMap = HashMap<Coordinates, char>;
 type ItemLocations = HashMap<char, Coordinates>;
@@ -272,9 +277,31 @@ fn print_map(map:&AreaMap, path:&Vec<Coordinates>) {
     }
 }
 
+// The maze with the shortest path drawn over its open tiles and the portals
+// in between.
+fn save_image(map:&AreaMap, path:&Vec<Coordinates>, file:&str, scale:usize) {
+    let mut picture = map.clone();
+    for position in path {
+        if picture.get(position) == Some(&'.') {
+            picture.insert(*position, '*');
+        }
+    }
+    let mut palette = Palette::new([0, 0, 0]).color('#', [90, 90, 90]).color('.', [220, 220, 220]).color('*', [30, 144, 255]);
+    for &c in map.values() {
+        if c != '#' && c != '.' && c != ' ' {
+            palette = palette.color(c, [220, 20, 60]);
+        }
+    }
+    Image::from_grid(&picture, &palette, scale).save(file);
+    println!("Saved the maze to {}", file);
+}
+
+// Arguments: 1 to print the maze with the shortest path, image=<file> and
+// scale=<pixels> to save it as a PNG or PPM.
 fn main() {
     let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let export = image::image_options(&mut args);
     let print = args.len() == 2 && &args[1] == "1";
     let lines: Vec<_> = data.split('\n').collect();
     let mut input:AreaMap = AreaMap::new();
@@ -317,11 +344,16 @@ fn main() {
     }
 
     let answer1:u64;
-    if print {
+    if print || export.is_some() {
         let mut came_from = CameFrom::new();
         answer1 = shortest_path1(outer_doors.get(&new_start_door).unwrap(), outer_doors.get(&new_finish_door).unwrap(), 0, &map, &mut VisitedLocations::new(), &mut came_from, x_max, y_max, &outer_doors, &inner_doors);
         let path = reconstruct_path(&came_from, outer_doors.get(&new_start_door).unwrap());
-        print_map(&map, &path);
+        if print {
+            print_map(&map, &path);
+        }
+        if let Some((file, scale)) = &export {
+            save_image(&map, &path, file, *scale);
+        }
     } else {
         answer1 = shortest_path2(new_start_door, new_finish_door, &map, &outer_doors, &inner_doors, x_max, y_max, false);
     }
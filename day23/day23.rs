@@ -1,301 +1,48 @@
-use std::fs;
-use std::usize;
-use std::collections::VecDeque;
-use std::collections::HashMap;
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Opcode {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    AdjustRelativeBase,
-    Return,
-    Unknown,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Status {
-    Running,
-    Finished,
-    Blocked,
-    Killed,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative,
-    Unknown,
-}
-
-#[derive(Clone, Debug)]
-struct Intcode {
-    status: Status,
-    memory: HashMap<usize, i64>,
-    program_counter: usize,
-    relative_base: i64,
-    inputs: VecDeque<i64>,
-    outputs: VecDeque<i64>,
-}
-
-impl Intcode {
-    fn new(program:Vec<i64>) -> Intcode {
-        let mut m:HashMap<usize, i64> = HashMap::new();
-        for (i, &v) in program.iter().enumerate() {
-            m.insert(i, v);
-        }
-        Intcode {
-            status: Status::Running,
-            memory: m,
-            program_counter: 0,
-            relative_base: 0,
-            inputs: VecDeque::new(),
-            outputs: VecDeque::new(),
-        }
-    }
-
-    fn input(&mut self, value:i64) {
-        self.inputs.push_back(value);
-    }
-
-    fn execute(&mut self) {
-        while self.status == Status::Running {
-            self.tick();
-        }
-    }
-
-    fn tick(&mut self) {
-        let instruction = self.memory[&self.program_counter];
-        let opcode = self.opcode(instruction);
-        match opcode {
-            Opcode::Add => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a + b);
-                self.program_counter += 4;
-            },
-            Opcode::Multiply => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a * b);
-                self.program_counter += 4;
-            },
-            Opcode::Input => {
-                if let Some(input) = self.inputs.pop_front() {
-                    self.store_position(1, input);
-                    self.program_counter += 2;
-                } else {
-                    self.status = Status::Blocked;
-                }
-            },
-            Opcode::Output => {
-                let output = self.get_parameter(1);
-                self.outputs.push_back(output);
-                self.program_counter += 2;
-            },
-            Opcode::JumpIfTrue => {
-                let condition = self.get_parameter(1);
-                if condition != 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::JumpIfFalse => {
-                let condition = self.get_parameter(1);
-                if condition == 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::LessThan => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a < b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::Equals => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a == b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::AdjustRelativeBase => {
-                let a = self.get_parameter(1);
-                self.relative_base += a;
-                self.program_counter += 2;
-            },
-            Opcode::Return => {
-                self.status = Status::Finished;
-            },
-            Opcode::Unknown => {
-                self.status = Status::Killed;
-            },
-        }
-    }
-
-    fn opcode(&mut self, instruction:i64) -> Opcode{
-        match instruction % 100 {
-            1 => Opcode::Add,
-            2 => Opcode::Multiply,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::JumpIfTrue,
-            6 => Opcode::JumpIfFalse,
-            7 => Opcode::LessThan,
-            8 => Opcode::Equals,
-            9 => Opcode::AdjustRelativeBase,
-            99 => Opcode::Return,
-            _ => Opcode::Unknown,
-        }
-    }
-
-    fn get_parameter(&mut self, offset:usize) -> i64 {
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => self.get_position(offset),
-            ParameterMode::Immediate => self.get(offset),
-            ParameterMode::Relative => self.get_relative(offset),
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-    }
-
-    fn store_position(&mut self, offset:usize, value:i64) {
-        let store_index:usize;
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Immediate => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Relative => store_index = (self.relative_base + self.memory[&(self.program_counter + offset)]) as usize,
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-
-        *self.memory.entry(store_index).or_insert(0) = value
-    }
-
-    fn get(&mut self, offset:usize) -> i64 {
-        *self.memory.entry(self.program_counter + offset).or_insert(0)
-    }
-
-    fn get_position(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset) as usize;
-        *self.memory.entry(v).or_insert(0)
-    }
-
-    fn get_relative(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset);
-        *self.memory.entry((self.relative_base + v) as usize).or_insert(0)
-    }
-
-    fn get_parameter_mode(&self, offset:usize) -> ParameterMode {
-        match (self.memory[&self.program_counter] / (10i64.pow(offset as u32 + 1))) % 10 {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            2 => ParameterMode::Relative,
-            _ => ParameterMode::Unknown,
-        }
-    }
-
-    fn blocked(&self) -> bool {
-        self.status == Status::Blocked
-    }
-
-    fn r#continue(&mut self) {
-        if self.blocked() && self.inputs.len() > 0 {
-            self.status = Status::Running;
-        }
-        self.execute();
-    }
-}
-
-const NUM_NICS:i64 = 50;
-const ALL_IDLE:u64 = !(!0 << NUM_NICS);
-
+use std::env;
+
+#[path = "../intcode/vm.rs"]
+mod vm;
+#[path = "../intcode/loader.rs"]
+mod loader;
+#[path = "../intcode/generate.rs"]
+mod generate;
+#[path = "../intcode/network.rs"]
+mod network;
+#[path = "../intcode/capture.rs"]
+mod capture;
+
+use network::{Config, Network};
+use capture::Capture;
+
+const MAX_ROUNDS:usize = 1_000_000;
+
+// Arguments: stats, capture <file>, and network options such as
+// schedule=random seed=7.
 fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let program: Vec<i64> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
-    let mut nics:Vec<Intcode> = Vec::new();
-    let mut inputs:Vec<VecDeque<i64>> = Vec::new();
-    let mut nat_x:i64 = 0;
-    let mut nat_y:i64 = 0;
-    let mut last_nat_y:i64 = 0;
-    let mut answer1 = false;
-
-    for i in 0..NUM_NICS {
-        let mut nic = Intcode::new(program.to_vec());
-        nic.input(i);
-        nic.execute();
-        nics.push(nic);
-        inputs.push(VecDeque::new());
-    }
-
-    let mut idle_nics:u64 = 0;
-
-    loop {
-        for (i, nic) in nics.iter_mut().enumerate() {
-            if nic.blocked() {
-                let mut idle = false;
-                if let Some(input) = inputs[i].pop_front() {
-                    nic.input(input);
-                } else {
-                    nic.input(-1);
-                    idle = true;
-                }
-                nic.r#continue();
-
-                if idle && nic.outputs.len() == 0 {
-                    idle_nics |= 1 << i;
-                } else {
-                    idle_nics &= !(1 << i);
-                }
-
-                while nic.outputs.len() > 0 {
-                    let addr = nic.outputs.pop_front().unwrap();
-                    let x = nic.outputs.pop_front().unwrap();
-                    let y = nic.outputs.pop_front().unwrap();
-                    if addr == 255 {
-                        nat_x = x;
-                        nat_y = y;
-
-                        if !answer1 {
-                            answer1 = true;
-                            println!("The Y value of the first packet sent to address 255 is {:?}", y);
-                        }
-                    } else {
-                        assert!(addr < NUM_NICS);
-                        inputs[addr as usize].push_back(x);
-                        inputs[addr as usize].push_back(y);
-                    }
-                }
-            }
-
-        }
-
-        if idle_nics == ALL_IDLE {
-            if last_nat_y == nat_y {
-                println!("The first Y value delivered by the NAT to the computer at address 0 twice in a row is {:?}", nat_y);
-                return;
-            }
-            last_nat_y = nat_y;
-            inputs[0].push_back(nat_x);
-            inputs[0].push_back(nat_y);
-        }
+    let program: Vec<i64> = loader::load_or_exit("input.txt");
+    let args: Vec<String> = env::args().collect();
+    let options:Vec<String> = args.iter().skip(1).filter(|a| a.contains('=')).cloned().collect();
+    let capture = args.iter().position(|a| a == "capture").and_then(|i| args.get(i + 1));
+    let config = Config::parse(&options).unwrap_or_else(|error| {
+        println!("{}", error);
+        std::process::exit(2);
+    });
+    let mut network = Network::new(&program, config);
+    network.set_capture(capture.is_some());
+
+    match network.nat_answers(MAX_ROUNDS) {
+        Some((first, repeated)) => {
+            println!("The Y value of the first packet sent to address 255 is {:?}", first);
+            println!("The first Y value delivered by the NAT to the computer at address 0 twice in a row is {:?}", repeated);
+        },
+        None => println!("The NAT never delivered the same Y value twice in a row"),
+    }
+    if args.iter().any(|a| a == "stats") {
+        network.report();
+    }
+    if let Some(path) = capture {
+        let capture = Capture::from_network(&network);
+        capture.save(path);
+        println!("Captured {} packets to {}", capture.packets.len(), path);
     }
 }
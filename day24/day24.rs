@@ -2,6 +2,16 @@ use std::fs;
 use std::env;
 use std::collections::HashMap;
 use std::collections::HashSet;
+
+#[path = "../intcode/render.rs"]
+mod render;
+#[path = "../intcode/cast.rs"]
+mod cast;
+#[allow(dead_code)]
+#[path = "../intcode/json.rs"]
+mod json;
+
+use render::{Renderer, Theme};
 // use std::collections::BTreeMap; // With sorted keys, but slower
 
 type Coordinates = (i32, i32);
@@ -11,6 +21,15 @@ const SIZE:i32 = 5;
 const ROW_NUM:[i32; 4] = [-1, 0, 1, 0];
 const COL_NUM:[i32; 4] = [ 0, 1, 0,-1];
 
+fn map_frame(map:&AreaMap) -> Vec<Vec<u8>> {
+    (0..SIZE).map(|y| (0..SIZE).map(|x| *map.get(&(x, y)).unwrap_or(&'.') as u8).collect()).collect()
+}
+
+fn theme() -> Theme {
+    Theme::new(1).tile(b'#', "#", Some(118))
+                 .tile(b'.', ".", Some(238))
+}
+
 fn print_map(map:&AreaMap, recurisve:bool) {
     for y in 0..SIZE {
         let mut row:Vec<char> = Vec::new();
@@ -157,8 +176,10 @@ fn recursive_adjacent_bugs(pos:Coordinates, level:i32, levels:&LevelMap, min_lvl
 
 fn main() {
     let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let cast = render::cast_options(&mut args);
     let print = args.len() == 2 && &args[1] == "1";
+    let animate = print || cast.is_some();
     let lines: Vec<_> = data.split('\n').collect();
     let mut initial_map = AreaMap::new();
     let mut map:AreaMap;
@@ -172,7 +193,12 @@ fn main() {
     map = initial_map.clone();
 
     // Part 1
-    loop {
+    let mut renderer = Renderer::new(theme());
+    renderer.set_max_fps(10);
+    if let Some((path, interval)) = &cast {
+        renderer.record(path, *interval);
+    }
+    for minute in 1.. {
         let mut new_map = map.clone();
         for y in 0..SIZE {
             for x in 0..SIZE {
@@ -194,10 +220,13 @@ fn main() {
             }
         }
 
+        if animate {
+            renderer.draw(map_frame(&new_map), &format!("Minute {}", minute));
+        }
         let state = map_state(&new_map);
         if map_states.contains(&state) {
-            if print {
-                print_map(&new_map, false);
+            if animate {
+                renderer.finish();
             }
             println!("The biodiversity rating for the first layout that appears twice is {:?}", biodiversity_rating(&new_map));
             break;
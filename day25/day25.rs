@@ -1,260 +1,22 @@
-use std::fs;
 use std::env;
-use std::usize;
+use std::time::Instant;
 use std::cmp::PartialOrd;
-use std::collections::VecDeque;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Opcode {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    AdjustRelativeBase,
-    Return,
-    Unknown,
-}
+#[path = "../intcode/vm.rs"]
+mod vm;
+#[path = "../intcode/loader.rs"]
+mod loader;
+#[path = "../intcode/disassembler.rs"]
+mod disassembler;
+#[path = "../intcode/session.rs"]
+mod session;
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Status {
-    Running,
-    Finished,
-    Blocked,
-    Killed,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative,
-    Unknown,
-}
-
-#[derive(Clone, Debug)]
-struct Intcode {
-    status: Status,
-    memory: HashMap<usize, i64>,
-    program_counter: usize,
-    relative_base: i64,
-    inputs: VecDeque<i64>,
-    outputs: VecDeque<i64>,
-    verbose: bool
-}
-
-impl Intcode {
-    fn new(program:Vec<i64>) -> Intcode {
-        let mut m:HashMap<usize, i64> = HashMap::new();
-        for (i, &v) in program.iter().enumerate() {
-            m.insert(i, v);
-        }
-        Intcode {
-            status: Status::Running,
-            memory: m,
-            program_counter: 0,
-            relative_base: 0,
-            inputs: VecDeque::new(),
-            outputs: VecDeque::new(),
-            verbose: false
-        }
-    }
-
-    fn set_verbose(&mut self, verbose:bool) {
-        self.verbose = verbose;
-    }
-
-    fn output_to_string(&self) -> String {
-        self.outputs.iter().map(|&c| c as u8 as char).collect::<String>()
-    }
-
-    fn get_output_string(&mut self) -> String {
-        if self.verbose {
-            self.print_output();
-        }
-        let output = self.output_to_string();
-        self.outputs.clear();
-        output
-    }
-
-    fn append_input(&mut self, values:&Vec<i64>) {
-        self.inputs.append(&mut VecDeque::from(values.to_vec()));
-    }
-
-    fn execute(&mut self) {
-        if self.inputs.len() > 0 && self.verbose {
-            self.print_input();
-        }
-        while self.status == Status::Running {
-            self.tick();
-        }
-    }
-
-    fn print_output(&self) {
-        print!("{}", self.output_to_string());
-    }
-
-    fn print_input(&self) {
-        print!("{}", self.inputs.iter().map(|&c| c as u8 as char).collect::<String>());
-    }
-
-    fn tick(&mut self) {
-        let instruction = self.memory[&self.program_counter];
-        let opcode = self.opcode(instruction);
-        match opcode {
-            Opcode::Add => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a + b);
-                self.program_counter += 4;
-            },
-            Opcode::Multiply => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a * b);
-                self.program_counter += 4;
-            },
-            Opcode::Input => {
-                if let Some(input) = self.inputs.pop_front() {
-                    self.store_position(1, input);
-                    self.program_counter += 2;
-                } else {
-                    self.status = Status::Blocked;
-                }
-            },
-            Opcode::Output => {
-                let output = self.get_parameter(1);
-                self.outputs.push_back(output);
-                self.program_counter += 2;
-            },
-            Opcode::JumpIfTrue => {
-                let condition = self.get_parameter(1);
-                if condition != 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::JumpIfFalse => {
-                let condition = self.get_parameter(1);
-                if condition == 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::LessThan => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a < b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::Equals => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a == b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::AdjustRelativeBase => {
-                let a = self.get_parameter(1);
-                self.relative_base += a;
-                self.program_counter += 2;
-            },
-            Opcode::Return => {
-                self.status = Status::Finished;
-            },
-            Opcode::Unknown => {
-                self.status = Status::Killed;
-            },
-        }
-    }
-
-    fn opcode(&mut self, instruction:i64) -> Opcode{
-        match instruction % 100 {
-            1 => Opcode::Add,
-            2 => Opcode::Multiply,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::JumpIfTrue,
-            6 => Opcode::JumpIfFalse,
-            7 => Opcode::LessThan,
-            8 => Opcode::Equals,
-            9 => Opcode::AdjustRelativeBase,
-            99 => Opcode::Return,
-            _ => Opcode::Unknown,
-        }
-    }
-
-    fn get_parameter(&mut self, offset:usize) -> i64 {
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => self.get_position(offset),
-            ParameterMode::Immediate => self.get(offset),
-            ParameterMode::Relative => self.get_relative(offset),
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-    }
-
-    fn store_position(&mut self, offset:usize, value:i64) {
-        let store_index:usize;
-        match self.get_parameter_mode(offset) {
-            ParameterMode::Position => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Immediate => store_index = self.memory[&(self.program_counter + offset)] as usize,
-            ParameterMode::Relative => store_index = (self.relative_base + self.memory[&(self.program_counter + offset)]) as usize,
-            ParameterMode::Unknown => panic!("Unknown paramter mode"),
-        }
-
-        *self.memory.entry(store_index).or_insert(0) = value
-    }
-
-    fn get(&mut self, offset:usize) -> i64 {
-        *self.memory.entry(self.program_counter + offset).or_insert(0)
-    }
-
-    fn get_position(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset) as usize;
-        *self.memory.entry(v).or_insert(0)
-    }
-
-    fn get_relative(&mut self, offset:usize) -> i64 {
-        let v = self.get(offset);
-        *self.memory.entry((self.relative_base + v) as usize).or_insert(0)
-    }
-
-    fn get_parameter_mode(&self, offset:usize) -> ParameterMode {
-        match (self.memory[&self.program_counter] / (10i64.pow(offset as u32 + 1))) % 10 {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            2 => ParameterMode::Relative,
-            _ => ParameterMode::Unknown,
-        }
-    }
-
-    fn blocked(&self) -> bool {
-        self.status == Status::Blocked
-    }
-
-    fn r#continue(&mut self) {
-        if self.blocked() && self.inputs.len() > 0 {
-            self.status = Status::Running;
-        }
-        self.execute();
-    }
-}
+use vm::Intcode;
+use vm::Coverage;
+use vm::AsciiEcho;
+use session::Session;
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 enum Action {
@@ -480,9 +242,6 @@ fn explore(droid:&mut Intcode, map:&mut AreaMap, pos:Coordinates, came_from:Dire
                 droid.r#continue();
                 let output = droid.output_to_string();
                 if output.contains("can't move") {
-                    if verbose {
-                        print!("{}", output);
-                    }
                     avoid_items.insert(last_item);
                     return true;
                 }
@@ -492,10 +251,10 @@ fn explore(droid:&mut Intcode, map:&mut AreaMap, pos:Coordinates, came_from:Dire
 
                 // Come back through the door
                 droid.append_input(&Action::Movement(dir.reverse()).to_instruction());
-                droid.set_verbose(false);
+                echo(droid, false);
                 droid.r#continue();
                 droid.outputs.clear();
-                droid.set_verbose(verbose);
+                echo(droid, verbose);
             }
         }
     }
@@ -503,33 +262,51 @@ fn explore(droid:&mut Intcode, map:&mut AreaMap, pos:Coordinates, came_from:Dire
     return false;
 }
 
-fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let program: Vec<i64> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
+// Prints the droid's conversation from here on, or stops printing it.
+fn echo(droid:&mut Intcode, verbose:bool) {
+    droid.clear_observers();
+    if verbose {
+        droid.add_observer(AsciiEcho);
+    }
+}
 
-    let args: Vec<String> = env::args().collect();
-    let verbose = args.len() == 2 && &args[1] == "1";
+fn new_droid(program:&Vec<i64>, verbose:bool, decode_cache:bool, coverage:bool, recording:bool) -> Intcode {
+    let mut droid = Intcode::new(program.to_vec());
+    echo(&mut droid, verbose);
+    droid.set_coverage(coverage);
+    droid.set_recording(recording);
+    droid.set_decode_cache(decode_cache);
+    droid.execute();
+    droid
+}
+
+// When `coverage` is given, it collects the instructions executed while
+// exploring the ship, over every restart of the exploration. When a session
+// is asked for, it holds the I/O of the droid that got through.
+fn find_password(program:&Vec<i64>, verbose:bool, decode_cache:bool, coverage:&mut Option<Coverage>, session:&mut Option<Session>) -> Option<String> {
     let mut map = AreaMap::new();
     let mut current_position = Coordinates(0, 0);
-    let mut droid = Intcode::new(program.to_vec());
+    let mut droid = new_droid(program, verbose, decode_cache, coverage.is_some(), session.is_some());
     let mut avoid_items = Items::new();
 
-    droid.set_verbose(verbose);
-    droid.execute();
     avoid_items.insert("infinite loop".to_string());
 
     while explore(&mut droid, &mut map, current_position, Direction::North, &mut avoid_items, verbose) {
         if verbose {
             println!("\n--------- Bad item, restarting exploration ---------");
         }
-        droid = Intcode::new(program.to_vec());
-        droid.set_verbose(verbose);
-        droid.execute();
+        if let Some(total) = coverage.as_mut() {
+            total.merge(droid.coverage().unwrap());
+        }
+        droid = new_droid(program, verbose, decode_cache, coverage.is_some(), session.is_some());
         map = AreaMap::new();
     }
 
+    if let Some(total) = coverage.as_mut() {
+        total.merge(droid.coverage().unwrap());
+        droid.set_coverage(false);
+    }
+
     if verbose {
         println!("\n--------- Everything mapped out ---------");
     }
@@ -563,7 +340,7 @@ fn main() {
 
     for combination in (0..2_usize.pow(inventory.len() as u32)).rev() {
         let mut droid_clone = droid.clone();
-        droid_clone.set_verbose(false);
+        echo(&mut droid_clone, false);
         for j in 0..inventory.len() {
             if (combination >> j) & 1 == 0 {
                 droid_clone.append_input(&Action::Drop(inventory[j].clone()).to_instruction());
@@ -571,7 +348,7 @@ fn main() {
             }
         }
         droid_clone.outputs.clear();
-        droid_clone.set_verbose(verbose);
+        echo(&mut droid_clone, verbose);
 
         if verbose {
             droid_clone.append_input(&Action::Inv.to_instruction());
@@ -586,10 +363,63 @@ fn main() {
                 println!("\n--------- Combination failed ---------\n");
             }
         } else {
+            if session.is_some() {
+                *session = Some(Session::from_run(program, &[], &droid_clone));
+            }
+            return Some(output);
+        }
+    }
+
+    None
+}
+
+fn benchmark(program:&Vec<i64>, runs:u32) {
+    for &decode_cache in [false, true].iter() {
+        let start = Instant::now();
+        for _ in 0..runs {
+            find_password(program, false, decode_cache, &mut None, &mut None);
+        }
+        println!("Decode cache {}: {:?} per run ({} runs)", if decode_cache { "on" } else { "off" }, start.elapsed() / runs, runs);
+    }
+}
+
+fn main() {
+    let program: Vec<i64> = loader::load_or_exit("input.txt");
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 2 && &args[1] == "bench" {
+        benchmark(&program, 10);
+        return;
+    }
+    if args.len() == 2 && &args[1] == "coverage" {
+        let mut coverage = Some(Coverage::default());
+        find_password(&program, false, true, &mut coverage, &mut None);
+        print!("{}", disassembler::listing(&program, coverage.as_ref()));
+        return;
+    }
+    if args.len() == 3 && &args[1] == "replay" {
+        match Session::load(&args[2]).and_then(|session| session.replay(&program)) {
+            Ok(()) => println!("Replay of {} matches", args[2]),
+            Err(error) => {
+                println!("Replay of {} differs: {}", args[2], error);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    let verbose = args.len() == 2 && &args[1] == "1";
+    let mut session = if args.len() == 3 && &args[1] == "record" { Some(Session::new()) } else { None };
+
+    match find_password(&program, verbose, true, &mut None, &mut session) {
+        Some(output) => {
             if !verbose {
                 println!("{}", output);
             }
-            break;
-        }
+            if let Some(session) = session {
+                session.save(&args[2]);
+                println!("Recorded {} inputs and {} outputs to {}", session.inputs.len(), session.outputs.len(), args[2]);
+            }
+        },
+        None => println!("No item combination got through the security checkpoint"),
     }
 }
@@ -1,6 +1,8 @@
-use std::fs;
 use std::collections::VecDeque;
 
+#[path = "../intcode/loader.rs"]
+mod loader;
+
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 enum Opcode {
     Add,
@@ -16,7 +18,7 @@ enum Opcode {
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Status {
+pub enum Status {
     Running,
     Finished,
     Blocked,
@@ -25,16 +27,16 @@ enum Status {
 
 
 #[derive(Clone, Debug)]
-struct Intcode {
-    status: Status,
-    memory: Vec<i32>,
+pub struct Intcode {
+    pub status: Status,
+    pub memory: Vec<i32>,
     program_counter: usize,
     inputs: VecDeque<i32>,
-    outputs: VecDeque<i32>,
+    pub outputs: VecDeque<i32>,
 }
 
 impl Intcode {
-    fn new(program:Vec<i32>) -> Intcode {
+    pub fn new(program:Vec<i32>) -> Intcode {
         Intcode {
             status: Status::Running,
             memory: program,
@@ -44,11 +46,11 @@ impl Intcode {
         }
     }
 
-    fn input(&mut self, value:i32) {
+    pub fn input(&mut self, value:i32) {
         self.inputs.push_back(value);
     }
 
-    fn execute(&mut self) {
+    pub fn execute(&mut self) {
         while self.status == Status::Running {
             self.tick();
         }
@@ -71,7 +73,7 @@ impl Intcode {
                 self.program_counter += 4;
             },
             Opcode::Input => {
-                if let Some(input) = self.inputs.pop_back() {
+                if let Some(input) = self.inputs.pop_front() {
                     self.store_position(1, input);
                     self.program_counter += 2;
                 } else {
@@ -173,10 +175,7 @@ impl Intcode {
 
 
 fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let program: Vec<i32> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
+    let program: Vec<i32> = loader::load_or_exit("input.txt").iter().map(|&v| v as i32).collect();
 
     for &system_id in vec![1, 5].iter() {
         let mut prog = Intcode::new(program.clone());
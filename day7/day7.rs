@@ -1,271 +1,140 @@
-use std::fs;
-use std::collections::VecDeque;
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Opcode {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    Return,
-    Unknown,
-}
-
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-enum Status {
-    Running,
-    Finished,
-    Blocked,
-    Killed,
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[path = "../intcode/vm.rs"]
+mod vm;
+#[path = "../intcode/loader.rs"]
+mod loader;
+#[path = "../intcode/pipeline.rs"]
+mod pipeline;
+
+use pipeline::Builder;
+
+const SLICE:usize = 10_000;
+const MAX_TICKS:usize = 100_000_000;
+
+// Every ordering of a list of phases, one at a time, by Heap's algorithm.
+struct Permutations {
+    phases: Vec<i64>,
+    counters: Vec<usize>,
+    index: usize,
+    started: bool,
 }
 
-
-#[derive(Clone, Debug)]
-struct Intcode {
-    status: Status,
-    memory: Vec<i32>,
-    program_counter: usize,
-    inputs: VecDeque<i32>,
-    outputs: VecDeque<i32>,
-}
-
-impl Intcode {
-    fn new(program:Vec<i32>) -> Intcode {
-        Intcode {
-            status: Status::Running,
-            memory: program,
-            program_counter: 0,
-            inputs: VecDeque::new(),
-            outputs: VecDeque::new(),
-        }
-    }
-
-    fn input(&mut self, value:i32) {
-        self.inputs.push_back(value);
-    }
-
-    fn execute(&mut self) {
-        while self.status == Status::Running {
-            self.tick();
-        }
+impl Permutations {
+    fn new(phases:Vec<i64>) -> Permutations {
+        Permutations { counters: vec![0; phases.len()], phases: phases, index: 1, started: false }
     }
+}
 
-    fn tick(&mut self) {
-        let instruction = self.memory[self.program_counter];
-        let opcode = self.opcode(instruction);
-        match opcode {
-            Opcode::Add => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a + b);
-                self.program_counter += 4;
-            },
-            Opcode::Multiply => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                self.store_position(3, a * b);
-                self.program_counter += 4;
-            },
-            Opcode::Input => {
-                if let Some(input) = self.inputs.pop_front() {
-                    self.store_position(1, input);
-                    self.program_counter += 2;
-                } else {
-                    self.status = Status::Blocked;
-                }
-            },
-            Opcode::Output => {
-                let output = self.get_parameter(1);
-                self.outputs.push_back(output);
-                self.program_counter += 2;
-            },
-            Opcode::JumpIfTrue => {
-                let condition = self.get_parameter(1);
-                if condition != 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::JumpIfFalse => {
-                let condition = self.get_parameter(1);
-                if condition == 0 {
-                    let jump = self.get_parameter(2) as usize;
-                    self.program_counter = jump;
-                } else {
-                    self.program_counter += 3;
-                }
-            },
-            Opcode::LessThan => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a < b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::Equals => {
-                let a = self.get_parameter(1);
-                let b = self.get_parameter(2);
-                if a == b {
-                    self.store_position(3, 1);
-                } else {
-                    self.store_position(3, 0);
-                }
-                self.program_counter += 4;
-            },
-            Opcode::Return => {
-                self.status = Status::Finished;
-            },
-            Opcode::Unknown => {
-                self.status = Status::Killed;
-            },
-        }
-    }
+impl Iterator for Permutations {
+    type Item = Vec<i64>;
 
-    fn opcode(&mut self, instruction:i32) -> Opcode{
-        match instruction % 100 {
-            1 => Opcode::Add,
-            2 => Opcode::Multiply,
-            3 => Opcode::Input,
-            4 => Opcode::Output,
-            5 => Opcode::JumpIfTrue,
-            6 => Opcode::JumpIfFalse,
-            7 => Opcode::LessThan,
-            8 => Opcode::Equals,
-            99 => Opcode::Return,
-            _ => Opcode::Unknown,
+    fn next(&mut self) -> Option<Vec<i64>> {
+        if !self.started {
+            self.started = true;
+            return Some(self.phases.clone());
         }
-    }
-
-    fn get_parameter(&self, offset:usize) -> i32 {
-        if self.is_immediate(offset) {
-            self.get(offset)
-        } else {
-            self.get_position(offset)
+        while self.index < self.phases.len() {
+            let i = self.index;
+            if self.counters[i] < i {
+                let j = if i % 2 == 0 { 0 } else { self.counters[i] };
+                self.phases.swap(j, i);
+                self.counters[i] += 1;
+                self.index = 1;
+                return Some(self.phases.clone());
+            }
+            self.counters[i] = 0;
+            self.index += 1;
         }
+        None
     }
+}
 
-    fn store_position(&mut self, offset:usize, value:i32) {
-        let store_index = self.memory[self.program_counter + offset] as usize;
-        self.memory[store_index] = value;
-    }
-
-    fn get(&self, offset:usize) -> i32 {
-        self.memory[self.program_counter + offset]
-    }
-
-    fn get_position(&self, offset:usize) -> i32 {
-        self.memory[self.get(offset) as usize]
-    }
-
-    fn is_immediate(&self, offset:usize) -> bool {
-        (self.memory[self.program_counter] / (10i32.pow(offset as u32 + 1))) % 10 == 1
-    }
-
-    fn finished(&self) -> bool {
-        self.status == Status::Finished
+// The signal the last amplifier sends for one phase setting. Settings that
+// stall, fault or send nothing are reported and skipped.
+fn thruster_signal(program:&Vec<i64>, setting:&Vec<i64>, feedback:bool) -> Option<i64> {
+    let run = Builder::amplifiers(program, setting, feedback)
+                      .and_then(|mut amps| amps.run(SLICE, MAX_TICKS).map(|outputs| outputs.clone()));
+    match run {
+        Ok(outputs) if !outputs.is_empty() => outputs.last().cloned(),
+        Ok(_) => {
+            println!("{:?}: no output", setting);
+            None
+        },
+        Err(stop) => {
+            println!("{:?}: {}", setting, stop);
+            None
+        },
     }
+}
 
-    fn blocked(&self) -> bool {
-        self.status == Status::Blocked
+// Prefers the higher signal and, between equal signals, the smaller
+// setting, so that the answer does not depend on how threads interleave.
+fn better(a:Option<(i64, Vec<i64>)>, b:Option<(i64, Vec<i64>)>) -> Option<(i64, Vec<i64>)> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if (b.0, &a.1) > (a.0, &b.1) { b } else { a }),
+        (a, b) => a.or(b),
     }
+}
 
-    fn r#continue(&mut self) {
-        if self.blocked() && self.inputs.len() > 0 {
-            self.status = Status::Running;
-        }
-        self.execute();
-    }
+// The highest signal over every ordering of the phases and the setting that
+// sends it, with the orderings shared out between threads as they go.
+fn max_thruster_signal(program:&Vec<i64>, phases:Vec<i64>, feedback:bool, threads:usize) -> Option<(i64, Vec<i64>)> {
+    let permutations = Arc::new(Mutex::new(Permutations::new(phases)));
+    let workers:Vec<_> = (0..threads.max(1)).map(|_| {
+        let permutations = permutations.clone();
+        let program = program.clone();
+        thread::spawn(move || {
+            let mut best:Option<(i64, Vec<i64>)> = None;
+            loop {
+                let setting = match permutations.lock().unwrap().next() {
+                    Some(setting) => setting,
+                    None => return best,
+                };
+                if let Some(signal) = thruster_signal(&program, &setting, feedback) {
+                    best = better(best, Some((signal, setting)));
+                }
+            }
+        })
+    }).collect();
+    workers.into_iter().map(|worker| worker.join().unwrap()).fold(None, better)
 }
 
-fn permute(list:&mut Vec<i32>, start_idx:usize, end_idx:usize, permutes:&mut Vec<Vec<i32>>) {
-    if start_idx == end_idx {
-        permutes.push(list.clone());
-    } else {
-        for i in start_idx..end_idx {
-            let mut tmp = list[start_idx];
-            list[start_idx] = list[i];
-            list[i] = tmp;
-            permute(list, start_idx + 1, end_idx, permutes);
-            tmp = list[start_idx];
-            list[start_idx] = list[i];
-            list[i] = tmp;
-        }
+// A range A..B of at least one phase.
+fn parse_range(text:&str) -> Option<Vec<i64>> {
+    let bounds:Vec<i64> = text.split("..").map(|v| v.trim().parse().ok()).collect::<Option<_>>()?;
+    match bounds.as_slice() {
+        &[start, end] if start < end => Some((start..end).collect()),
+        _ => None,
     }
 }
 
+// Arguments: part1=A..B and part2=A..B for other phase ranges, and so chain
+// lengths, and threads=N.
 fn main() {
-    let data = fs::read_to_string("input.txt").expect("Unable to read file");
-    let program: Vec<i32> = data.split(',')
-                                    .map(|s| s.parse().unwrap())
-                                    .collect();
-
-    let mut phase_setting_permutations:Vec<Vec<i32>> = vec![];
-    permute(&mut (0..5).collect(), 0, 5, &mut phase_setting_permutations);
-    let mut max_thruster_signal = 0;
-
-    for setting in phase_setting_permutations {
-        let mut last_output:i32 = 0;
-
-        for phase in setting {
-            let mut amp = Intcode::new(program.clone());
-            amp.input(phase);
-            amp.input(last_output);
-            amp.execute();
-            last_output = amp.outputs.pop_back().expect("Nan");
-        }
-        if last_output > max_thruster_signal {
-            max_thruster_signal = last_output;
+    let program: Vec<i64> = loader::load_or_exit("input.txt");
+    let mut parts = vec![(1, (0..5).collect::<Vec<i64>>(), false), (2, (5..10).collect(), true)];
+    let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    for argument in env::args().skip(1) {
+        let mut option = argument.splitn(2, '=');
+        let (key, value) = (option.next().unwrap(), option.next().unwrap_or(""));
+        match (key, parse_range(value), value.parse::<usize>()) {
+            ("part1", Some(range), _) => parts[0].1 = range,
+            ("part2", Some(range), _) => parts[1].1 = range,
+            ("threads", _, Ok(n)) if n > 0 => threads = n,
+            _ => {
+                println!("Unknown argument {:?}, expected part1=A..B, part2=A..B with A < B or threads=N", argument);
+                std::process::exit(2);
+            },
         }
     }
 
-    println!("Part1: The highest signal that can be sent to the thrusters is {:?}", max_thruster_signal);
-
-    let mut phase_setting_permutations:Vec<Vec<i32>> = vec![];
-    permute(&mut (5..10).collect(), 0, 5, &mut phase_setting_permutations);
-    max_thruster_signal = 0;
-
-    for setting in phase_setting_permutations {
-        let mut last_output:i32 = 0;
-
-        let mut amps:Vec<Intcode> = vec![];
-
-        for x in 0..5 {
-            let mut amp = Intcode::new(program.clone());
-            amp.input(setting[x]);
-            amps.push(amp);
-        }
-
-        let mut finished = false;
-
-        while !finished {
-            for amp in &mut amps {
-                amp.input(last_output);
-                if amp.blocked() {
-                    amp.r#continue();
-                } else {
-                    amp.execute();
-                }
-                match amp.outputs.pop_back() {
-                    Some(value) => last_output = value,
-                    None => panic!("{:?} no output", setting),
-                }
-                finished = amp.finished();
-            }
-        }
-
-        if last_output > max_thruster_signal {
-            max_thruster_signal = last_output;
+    for (part, phases, feedback) in parts {
+        match max_thruster_signal(&program, phases, feedback, threads) {
+            Some((signal, setting)) => println!("Part{}: The highest signal that can be sent to the thrusters is {:?} with phase setting {:?}", part, signal, setting),
+            None => println!("Part{}: No phase setting sends a signal to the thrusters", part),
         }
     }
-
-    println!("Part2: The highest signal that can be sent to the thrusters is {:?}", max_thruster_signal);
-}
\ No newline at end of file
+}
@@ -1,6 +1,18 @@
 use std::collections::HashMap;
+use std::env;
 
+#[path = "../intcode/image.rs"]
+mod image;
+#[path = "../intcode/ocr.rs"]
+mod ocr;
+
+use image::{Image, Palette};
+
+// Arguments: image=<file> and scale=<pixels> to save the decoded message as
+// a PNG or PPM.
 fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    let export = image::image_options(&mut args);
     let input = include_str!("input.txt").trim_right();
     const IMAGE_WIDTH:usize = 25;
     const IMAGE_HEIGHT:usize = 6;
@@ -54,4 +66,21 @@ fn main() {
         let line:String = row.iter().map(|i| if i == &0 { "██" } else { "  "}).collect();
         println!("{}", line);
     }
-}
\ No newline at end of file
+    let bitmap:Vec<Vec<bool>> = image.iter().map(|row| row.iter().map(|&pixel| pixel == 1).collect()).collect();
+    match ocr::read(&bitmap) {
+        Ok(text) => println!("The message is {}", text),
+        Err(error) => println!("The message could not be read: {}", error),
+    }
+
+    if let Some((path, scale)) = export {
+        let mut pixels:HashMap<(i32, i32), u8> = HashMap::new();
+        for (y, row) in image.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                pixels.insert((x as i32, y as i32), pixel);
+            }
+        }
+        let palette = Palette::new([128, 128, 128]).color(0, [0, 0, 0]).color(1, [255, 255, 255]);
+        Image::from_grid(&pixels, &palette, scale).save(&path);
+        println!("Saved the message to {}", path);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use super::disassembler;
+use super::vm::{Intcode, MemoryTrace, SelfModification, Status};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Region {
+    Code,
+    SelfModifiedCode,
+    ReadOnlyData,
+    MutableData,
+    Untouched,
+}
+
+impl Region {
+    fn to_string(&self) -> &'static str {
+        match self {
+            Region::Code => "code",
+            Region::SelfModifiedCode => "self-modified code",
+            Region::ReadOnlyData => "read-only data",
+            Region::MutableData => "mutable data",
+            Region::Untouched => "untouched",
+        }
+    }
+}
+
+pub struct Analysis {
+    pub status: Status,
+    pub regions: Vec<(usize, usize, Region)>,
+    pub self_modifications: Vec<SelfModification>,
+    pub static_instructions: BTreeSet<usize>,
+    pub executed_instructions: BTreeSet<usize>,
+}
+
+impl Analysis {
+    pub fn self_modifying(&self) -> bool {
+        self.self_modifications.len() > 0
+    }
+}
+
+// Code counts as self-modified only when a write landed on it after it was
+// executed; a word that is written first and then run is just code.
+fn classify(address:usize, trace:&MemoryTrace, modified:&BTreeSet<usize>) -> Region {
+    let written = trace.written.contains(&address);
+    if trace.executed.contains(&address) {
+        if modified.contains(&address) { Region::SelfModifiedCode } else { Region::Code }
+    } else if written {
+        Region::MutableData
+    } else if trace.read.contains(&address) {
+        Region::ReadOnlyData
+    } else {
+        Region::Untouched
+    }
+}
+
+// Runs the program with the given inputs and memory tracing enabled.
+pub fn trace(program:&Vec<i64>, inputs:&Vec<i64>, max_ticks:usize) -> Intcode {
+    let mut computer = Intcode::new(program.clone());
+    computer.set_memory_trace(true);
+    computer.append_input(inputs);
+    let mut ticks = 0;
+    while computer.status == Status::Running && ticks < max_ticks {
+        computer.tick();
+        ticks += 1;
+    }
+    computer
+}
+
+// Runs the program with the given inputs and classifies every address of the
+// image plus everything the run touched beyond it into contiguous regions.
+pub fn analyze(program:&Vec<i64>, inputs:&Vec<i64>, max_ticks:usize) -> Analysis {
+    let computer = trace(program, inputs, max_ticks);
+    let trace = computer.memory_trace().unwrap();
+
+    let mut addresses:BTreeSet<usize> = (0..program.len()).collect();
+    addresses.extend(trace.executed.iter());
+    addresses.extend(trace.read.iter());
+    addresses.extend(trace.written.iter());
+
+    let modified:BTreeSet<usize> = trace.self_modifications.iter().map(|m| m.address).collect();
+    let mut regions:Vec<(usize, usize, Region)> = Vec::new();
+    for address in addresses {
+        let region = classify(address, trace, &modified);
+        match regions.last_mut() {
+            Some(last) if last.1 + 1 == address && last.2 == region => last.1 = address,
+            _ => regions.push((address, address, region)),
+        }
+    }
+
+    Analysis {
+        status: computer.status.clone(),
+        regions: regions,
+        self_modifications: trace.self_modifications.clone(),
+        static_instructions: disassembler::reachable(program).keys().cloned().collect(),
+        executed_instructions: trace.instructions.clone(),
+    }
+}
+
+pub fn report(analysis:&Analysis) {
+    println!("Run ended with status {:?}", analysis.status);
+    println!();
+
+    let mut totals:BTreeMap<&str, usize> = BTreeMap::new();
+    for &(start, end, region) in &analysis.regions {
+        if start == end {
+            println!("{:>8}          {}", start, region.to_string());
+        } else {
+            println!("{:>8}..{:<8} {}", start, end, region.to_string());
+        }
+        *totals.entry(region.to_string()).or_insert(0) += end - start + 1;
+    }
+    println!();
+    for (region, total) in &totals {
+        println!("{:>8} words of {}", total, region);
+    }
+    println!();
+
+    let written_code = totals.get(Region::SelfModifiedCode.to_string()).cloned().unwrap_or(0);
+    println!("Writes into already executed instructions: {}", analysis.self_modifications.len());
+    for modification in analysis.self_modifications.iter().take(20) {
+        println!("  instruction at {} wrote {} to {}", modification.program_counter, modification.value, modification.address);
+    }
+    if analysis.self_modifications.len() > 20 {
+        println!("  ...");
+    }
+    if analysis.self_modifying() {
+        println!("Decode cache: relies on invalidation");
+    } else {
+        println!("Decode cache: safe");
+    }
+    if written_code > 0 {
+        println!("Translation: {} code words are written at run time and fall back to the interpreter", written_code);
+    } else {
+        println!("Translation: safe");
+    }
+
+    let missed:Vec<&usize> = analysis.executed_instructions.difference(&analysis.static_instructions).collect();
+    println!("Statically reachable instructions: {}, executed: {}, executed but not found statically: {}",
+             analysis.static_instructions.len(), analysis.executed_instructions.len(), missed.len());
+}
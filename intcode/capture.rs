@@ -0,0 +1,155 @@
+#![allow(dead_code)]
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fs;
+
+use super::network::{Network, Packet, Special};
+use super::vm::{Intcode, Status};
+
+// The packets of a network run in the order they were routed. Saved as
+// text, one entry per line, with the size of the network and its broadcast
+// addresses first so that a replay knows which packets reached a node:
+//
+//     nodes 50
+//     broadcast 70
+//     packet 12 4 255 102059 20654
+//
+// where a packet is its tick, source, destination, x and y.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    pub nodes: usize,
+    pub broadcast: BTreeSet<i64>,
+    pub packets: Vec<Packet>,
+}
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture { nodes: 0, broadcast: BTreeSet::new(), packets: Vec::new() }
+    }
+
+    // The capture of a network that has been capturing since it booted.
+    pub fn from_network(network:&Network) -> Capture {
+        let packets = network.capture().expect("Capture is not enabled");
+        Capture {
+            nodes: network.nodes.len(),
+            broadcast: network.config.special.iter().filter(|(_, &s)| s == Special::Broadcast).map(|(&a, _)| a).collect(),
+            packets: packets.clone(),
+        }
+    }
+
+    pub fn save(&self, path:&str) {
+        let mut text = format!("nodes {}\n", self.nodes);
+        for address in &self.broadcast {
+            text.push_str(&format!("broadcast {}\n", address));
+        }
+        for p in &self.packets {
+            text.push_str(&format!("packet {} {} {} {} {}\n", p.tick, p.source, p.destination, p.x, p.y));
+        }
+        fs::write(path, text).expect("Unable to write file");
+    }
+
+    pub fn load(path:&str) -> Result<Capture, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut capture = Capture::new();
+        for (n, line) in text.lines().enumerate() {
+            let error = || format!("{}: line {}: unable to parse {:?}", path, n + 1, line);
+            let fields:Vec<&str> = line.split_whitespace().collect();
+            let numbers:Vec<i64> = fields.iter().skip(1).map(|v| v.parse().map_err(|_| error())).collect::<Result<_, _>>()?;
+            match (fields.get(0).cloned(), numbers.len()) {
+                (Some("nodes"), 1) => capture.nodes = numbers[0] as usize,
+                (Some("broadcast"), 1) => { capture.broadcast.insert(numbers[0]); },
+                (Some("packet"), 5) => capture.packets.push(Packet {
+                    tick: numbers[0] as u64, source: numbers[1], destination: numbers[2], x: numbers[3], y: numbers[4],
+                }),
+                (None, _) => {},
+                _ => return Err(error()),
+            }
+        }
+        Ok(capture)
+    }
+
+    // The packets a node sent or was sent, or all of them.
+    pub fn filter(&self, address:Option<i64>) -> Vec<Packet> {
+        self.packets.iter().filter(|p| address.map_or(true, |a| p.source == a || p.destination == a)).cloned().collect()
+    }
+
+    // The packets that reached a node: sent to it, or broadcast by another.
+    pub fn delivered(&self, address:i64) -> Vec<Packet> {
+        self.packets.iter().filter(|p| p.destination == address || (self.broadcast.contains(&p.destination) && p.source != address))
+                    .cloned().collect()
+    }
+
+    // Prints the packets in order with the round of the network they were
+    // sent in, and how much traffic each address saw.
+    pub fn timeline(&self, address:Option<i64>) {
+        let packets = self.filter(address);
+        println!("{:>8} {:>6} {:>7}    {:<11} {:>16} {:>16}", "tick", "round", "source", "destination", "x", "y");
+        for p in &packets {
+            let round = if self.nodes > 0 { (p.tick.max(1) - 1) / self.nodes as u64 } else { 0 };
+            println!("{:>8} {:>6} {:>7} -> {:<11} {:>16} {:>16}", p.tick, round, p.source, p.destination, p.x, p.y);
+        }
+        println!();
+        let mut addresses:BTreeSet<i64> = BTreeSet::new();
+        for p in &packets {
+            addresses.insert(p.source);
+            addresses.insert(p.destination);
+        }
+        for a in addresses.iter().filter(|&&a| address.map_or(true, |b| a == b)) {
+            let sent = packets.iter().filter(|p| p.source == *a).count();
+            let received = packets.iter().filter(|p| p.destination == *a).count();
+            println!("address {:>4}: {} sent, {} received", a, sent, received);
+        }
+        println!("{} of {} packets", packets.len(), self.packets.len());
+    }
+
+    // Boots a single node at `address` and queues the captured packets that
+    // reached it, each from the tick after it was sent. The node gets a turn
+    // every tick up to the end of the capture, reading one value of its
+    // queue, or -1, as on the network. Returns what the node sent.
+    pub fn inject(&self, program:&Vec<i64>, address:i64, max_ticks:usize) -> Vec<Packet> {
+        let delivered = self.delivered(address);
+        let end = self.packets.last().map_or(0, |p| p.tick) + 1;
+        let mut node = Intcode::new(program.clone());
+        node.input(address);
+        node.run_for(max_ticks);
+        let mut queue:VecDeque<i64> = VecDeque::new();
+        let mut next = 0;
+        let mut sent:Vec<Packet> = Vec::new();
+        for tick in 1..=end {
+            while next < delivered.len() && delivered[next].tick < tick {
+                queue.push_back(delivered[next].x);
+                queue.push_back(delivered[next].y);
+                next += 1;
+            }
+            match node.status {
+                Status::Blocked => node.input(queue.pop_front().unwrap_or(-1)),
+                Status::Running => {},
+                _ => break,
+            }
+            node.run_for(max_ticks);
+            while node.outputs.len() >= 3 {
+                let destination = node.outputs.pop_front().unwrap();
+                let x = node.outputs.pop_front().unwrap();
+                let y = node.outputs.pop_front().unwrap();
+                sent.push(Packet { source: address, destination: destination, x: x, y: y, tick: tick });
+            }
+        }
+        sent
+    }
+
+    // Replays the packets a node received and checks that it sends the same
+    // packets, in the same order, as it did in the capture. Ticks are not
+    // compared, as the node runs on its own and answers sooner.
+    pub fn replay(&self, program:&Vec<i64>, address:i64, max_ticks:usize) -> Result<Vec<Packet>, String> {
+        let sent = self.inject(program, address, max_ticks);
+        let captured:Vec<Packet> = self.packets.iter().filter(|p| p.source == address).cloned().collect();
+        let key = |p:&Packet| (p.destination, p.x, p.y);
+        if let Some(i) = (0..sent.len().min(captured.len())).find(|&i| key(&sent[i]) != key(&captured[i])) {
+            return Err(format!("packet {} is {:?}, captured {:?} at tick {}", i + 1, key(&sent[i]), key(&captured[i]), captured[i].tick));
+        }
+        if sent.len() < captured.len() {
+            return Err(format!("the node sent {} packets, {} captured", sent.len(), captured.len()));
+        }
+        Ok(sent)
+    }
+}
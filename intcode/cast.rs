@@ -0,0 +1,67 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::json::{self, Json};
+
+// An asciicast v2 recording: a header line with the terminal size, then one
+// line per frame with its time in seconds and what was written to the
+// terminal.
+//
+//     {"version":2,"width":120,"height":25,"timestamp":1576454400}
+//     [0.000000, "o", "\u001b[2J\u001b[1;1H..."]
+//
+// Frames are either a fixed interval apart or stamped with the time they
+// were drawn.
+pub struct Cast {
+    interval: Option<Duration>,
+    started: Instant,
+    timestamp: u64,
+    pub width: usize,
+    pub height: usize,
+    pub events: Vec<(f64, String)>,
+}
+
+impl Cast {
+    pub fn new(interval:Option<Duration>) -> Cast {
+        Cast {
+            interval: interval,
+            started: Instant::now(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            width: 0,
+            height: 0,
+            events: Vec::new(),
+        }
+    }
+
+    // Adds what was written for a frame that takes up to this many columns
+    // and rows.
+    pub fn frame(&mut self, output:String, columns:usize, rows:usize) {
+        let time = match self.interval {
+            Some(interval) => interval.as_secs_f64() * self.events.len() as f64,
+            None => self.started.elapsed().as_secs_f64(),
+        };
+        self.width = self.width.max(columns);
+        self.height = self.height.max(rows);
+        self.events.push((time, output));
+    }
+
+    pub fn to_text(&self) -> String {
+        let header = json::object(vec![
+            ("version", Json::Number(2)),
+            ("width", Json::Number(self.width.max(1) as i64)),
+            ("height", Json::Number(self.height.max(1) as i64)),
+            ("timestamp", Json::Number(self.timestamp as i64)),
+        ]);
+        let mut text = format!("{}\n", header);
+        for (time, output) in &self.events {
+            text.push_str(&format!("[{:.6}, \"o\", {}]\n", time, json::string(output)));
+        }
+        text
+    }
+
+    pub fn save(&self, path:&str) {
+        fs::write(path, self.to_text()).expect("Unable to write file");
+    }
+}
@@ -0,0 +1,785 @@
+use std::collections::HashMap;
+
+// Compiler for a small C-like language targeting Intcode.
+//
+//     fn main() {
+//         let n = input();
+//         while (n > 0) {
+//             output(square(n));
+//             n = n - 1;
+//         }
+//     }
+//
+//     fn square(x) { return x * x; }
+//
+// Every value is an integer. The relative base points at the current stack
+// frame, which holds the return address in slot 0, the parameters in the
+// following slots and then the locals and expression temporaries. A call
+// stores the arguments and return address past the caller's frame, moves the
+// relative base there with AdjustRelativeBase and jumps; the callee returns
+// through slot 0 and leaves its result in a fixed memory cell.
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Symbol(&'static str),
+    End,
+}
+
+const SYMBOLS:[&str; 19] = ["<=", ">=", "==", "!=", "&&", "||", "(", ")", "{", "}", ",", ";", "=", "+", "-", "*", "<", ">", "!"];
+
+fn tokenize(source:&str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens:Vec<(Token, usize)> = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let line_number = n + 1;
+        let code = match line.find("//") {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        let chars:Vec<char> = code.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_digit() {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let text:String = chars[start..i].iter().collect();
+                match text.parse() {
+                    Ok(value) => tokens.push((Token::Number(value), line_number)),
+                    Err(_) => return Err(format!("line {}: number {} is too large", line_number, text)),
+                }
+            } else if c.is_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((Token::Identifier(chars[start..i].iter().collect()), line_number));
+            } else {
+                let rest:String = chars[i..].iter().take(2).collect();
+                match SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+                    Some(symbol) => {
+                        tokens.push((Token::Symbol(symbol), line_number));
+                        i += symbol.len();
+                    },
+                    None => return Err(format!("line {}: unexpected character {:?}", line_number, c)),
+                }
+            }
+        }
+    }
+    let last_line = source.lines().count();
+    tokens.push((Token::End, last_line));
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Expression {
+    Number(i64),
+    Variable(String, usize),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+    Call(String, Vec<Expression>, usize),
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Let(String, Expression),
+    Assign(String, Expression, usize),
+    If(Expression, Block, Block),
+    While(Expression, Block),
+    Return(Option<Expression>),
+    Expression(Expression),
+}
+
+// Statements with the source line each one starts on.
+type Block = Vec<(usize, Statement)>;
+
+#[derive(Clone, Debug)]
+struct Function {
+    name: String,
+    parameters: Vec<String>,
+    body: Block,
+    line: usize,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+const KEYWORDS:[&str; 6] = ["fn", "let", "if", "else", "while", "return"];
+
+// Binary operators from the loosest to the tightest binding level.
+const PRECEDENCE:[&[&str]; 5] = [&["||"], &["&&"], &["==", "!="], &["<", "<=", ">", ">="], &["+", "-"]];
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, expected:&str) -> Result<T, String> {
+        Err(format!("line {}: expected {}, found {:?}", self.line(), expected, self.peek()))
+    }
+
+    fn is_symbol(&self, symbol:&str) -> bool {
+        match self.peek() {
+            Token::Symbol(s) => *s == symbol,
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword:&str) -> bool {
+        match self.peek() {
+            Token::Identifier(name) => name == keyword,
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol:&str) -> Result<(), String> {
+        if self.is_symbol(symbol) {
+            self.next();
+            Ok(())
+        } else {
+            self.error(&format!("{:?}", symbol))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword:&str) -> Result<(), String> {
+        if self.is_keyword(keyword) {
+            self.next();
+            Ok(())
+        } else {
+            self.error(keyword)
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        match self.peek().clone() {
+            Token::Identifier(ref name) if !KEYWORDS.contains(&name.as_str()) => {
+                self.next();
+                Ok(name.clone())
+            },
+            _ => self.error("an identifier"),
+        }
+    }
+
+    fn program(&mut self) -> Result<Vec<Function>, String> {
+        let mut functions:Vec<Function> = Vec::new();
+        while *self.peek() != Token::End {
+            functions.push(self.function()?);
+        }
+        Ok(functions)
+    }
+
+    fn function(&mut self) -> Result<Function, String> {
+        let line = self.line();
+        self.expect_keyword("fn")?;
+        let name = self.identifier()?;
+        let mut parameters:Vec<String> = Vec::new();
+        self.expect_symbol("(")?;
+        while !self.is_symbol(")") {
+            if parameters.len() > 0 {
+                self.expect_symbol(",")?;
+            }
+            parameters.push(self.identifier()?);
+        }
+        self.next();
+        let body = self.block()?;
+        Ok(Function { name: name, parameters: parameters, body: body, line: line })
+    }
+
+    fn block(&mut self) -> Result<Block, String> {
+        let mut statements:Block = Vec::new();
+        self.expect_symbol("{")?;
+        while !self.is_symbol("}") {
+            if *self.peek() == Token::End {
+                return self.error("\"}\"");
+            }
+            let line = self.line();
+            statements.push((line, self.statement()?));
+        }
+        self.next();
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.is_keyword("let") {
+            self.next();
+            let name = self.identifier()?;
+            self.expect_symbol("=")?;
+            let value = self.expression(0)?;
+            self.expect_symbol(";")?;
+            Ok(Statement::Let(name, value))
+        } else if self.is_keyword("if") {
+            self.next();
+            self.expect_symbol("(")?;
+            let condition = self.expression(0)?;
+            self.expect_symbol(")")?;
+            let then = self.block()?;
+            let mut otherwise:Block = Vec::new();
+            if self.is_keyword("else") {
+                self.next();
+                if self.is_keyword("if") {
+                    let line = self.line();
+                    otherwise.push((line, self.statement()?));
+                } else {
+                    otherwise = self.block()?;
+                }
+            }
+            Ok(Statement::If(condition, then, otherwise))
+        } else if self.is_keyword("while") {
+            self.next();
+            self.expect_symbol("(")?;
+            let condition = self.expression(0)?;
+            self.expect_symbol(")")?;
+            Ok(Statement::While(condition, self.block()?))
+        } else if self.is_keyword("return") {
+            self.next();
+            let mut value = None;
+            if !self.is_symbol(";") {
+                value = Some(self.expression(0)?);
+            }
+            self.expect_symbol(";")?;
+            Ok(Statement::Return(value))
+        } else {
+            let line = self.line();
+            let is_assignment = match (&self.tokens[self.position].0, &self.tokens[self.position + 1].0) {
+                (Token::Identifier(_), Token::Symbol("=")) => true,
+                _ => false,
+            };
+            if is_assignment {
+                let name = self.identifier()?;
+                self.next();
+                let value = self.expression(0)?;
+                self.expect_symbol(";")?;
+                Ok(Statement::Assign(name, value, line))
+            } else {
+                let value = self.expression(0)?;
+                self.expect_symbol(";")?;
+                Ok(Statement::Expression(value))
+            }
+        }
+    }
+
+    fn expression(&mut self, level:usize) -> Result<Expression, String> {
+        if level == PRECEDENCE.len() {
+            return self.term();
+        }
+        let mut left = self.expression(level + 1)?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol(s) if PRECEDENCE[level].contains(s) => *s,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.expression(level + 1)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression, String> {
+        let mut left = self.unary()?;
+        while self.is_symbol("*") {
+            self.next();
+            let right = self.unary()?;
+            left = Expression::Binary("*", Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.is_symbol("-") {
+            self.next();
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        if self.is_symbol("!") {
+            self.next();
+            return Ok(Expression::Not(Box::new(self.unary()?)));
+        }
+        let line = self.line();
+        match self.peek().clone() {
+            Token::Number(value) => {
+                self.next();
+                Ok(Expression::Number(value))
+            },
+            Token::Symbol("(") => {
+                self.next();
+                let value = self.expression(0)?;
+                self.expect_symbol(")")?;
+                Ok(value)
+            },
+            Token::Identifier(_) => {
+                let name = self.identifier()?;
+                if !self.is_symbol("(") {
+                    return Ok(Expression::Variable(name, line));
+                }
+                self.next();
+                let mut arguments:Vec<Expression> = Vec::new();
+                while !self.is_symbol(")") {
+                    if arguments.len() > 0 {
+                        self.expect_symbol(",")?;
+                    }
+                    arguments.push(self.expression(0)?);
+                }
+                self.next();
+                Ok(Expression::Call(name, arguments, line))
+            },
+            _ => self.error("an expression"),
+        }
+    }
+}
+
+// The source line and function of each address, kept as the start addresses
+// of their code in ascending order. In a symbol map file every entry is a
+// line of its own: `line <address> <line>` or `function <address> <name>`.
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    pub lines: Vec<(usize, usize)>,
+    pub functions: Vec<(usize, String)>,
+}
+
+impl Symbols {
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (address, name) in &self.functions {
+            text.push_str(&format!("function {} {}\n", address, name));
+        }
+        for (address, line) in &self.lines {
+            text.push_str(&format!("line {} {}\n", address, line));
+        }
+        text
+    }
+
+    pub fn from_text(text:&str) -> Result<Symbols, String> {
+        let mut symbols = Symbols::default();
+        for (n, entry) in text.lines().enumerate() {
+            let fields:Vec<&str> = entry.split_whitespace().collect();
+            let address = fields.get(1).and_then(|a| a.parse().ok());
+            match (fields.get(0), address, fields.get(2)) {
+                (None, _, _) => {},
+                (Some(&"function"), Some(address), Some(name)) => symbols.functions.push((address, name.to_string())),
+                (Some(&"line"), Some(address), Some(line)) if line.parse::<usize>().is_ok() => {
+                    symbols.lines.push((address, line.parse().unwrap()));
+                },
+                _ => return Err(format!("line {}: expected function <address> <name> or line <address> <line>", n + 1)),
+            }
+        }
+        symbols.functions.sort();
+        symbols.lines.sort_by_key(|&(address, _)| address);
+        Ok(symbols)
+    }
+
+    // The line whose code contains the address. Where several lines start at
+    // the same address the innermost, which comes last, wins.
+    pub fn line_at(&self, address:usize) -> Option<usize> {
+        self.lines.iter().take_while(|&&(start, _)| start <= address).last().map(|&(_, line)| line)
+    }
+
+    pub fn function_at(&self, address:usize) -> Option<&str> {
+        self.functions.iter().take_while(|&&(start, _)| start <= address).last().map(|(_, name)| name.as_str())
+    }
+
+    // The first address of the line, or of the next line below it that has
+    // code of its own.
+    pub fn address_of(&self, line:usize) -> Option<(usize, usize)> {
+        self.lines.iter().filter(|&&(_, l)| l >= line).min_by_key(|&&(address, l)| (l, address)).map(|&(address, l)| (address, l))
+    }
+}
+
+// A memory word whose value may depend on a label or a frame size that is
+// only known once the whole program has been generated.
+#[derive(Clone, Debug)]
+enum Word {
+    Value(i64),
+    Symbol(String, i64, i64),
+}
+
+impl Word {
+    fn label(name:&str) -> Word {
+        Word::Symbol(name.to_string(), 1, 0)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Operand {
+    Immediate(Word),
+    Position(Word),
+    Relative(Word),
+}
+
+impl Operand {
+    fn mode(&self) -> i64 {
+        match self {
+            Operand::Position(_) => 0,
+            Operand::Immediate(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+
+    fn word(&self) -> Word {
+        match self {
+            Operand::Immediate(w) | Operand::Position(w) | Operand::Relative(w) => w.clone(),
+        }
+    }
+
+    fn slot(slot:i64) -> Operand {
+        Operand::Relative(Word::Value(slot))
+    }
+}
+
+const RETURN_VALUE:&str = "__return_value";
+const STACK:&str = "__stack";
+const HALT:&str = "__halt";
+
+struct Generator {
+    code: Vec<Word>,
+    symbols: HashMap<String, i64>,
+    functions: HashMap<String, usize>,
+    labels: usize,
+    function: String,
+    scopes: Vec<HashMap<String, i64>>,
+    next_slot: i64,
+    frame_size: i64,
+    source_map: Symbols,
+}
+
+impl Generator {
+    fn emit(&mut self, opcode:i64, operands:Vec<Operand>) {
+        let mut instruction = opcode;
+        for (i, operand) in operands.iter().enumerate() {
+            instruction += operand.mode() * 10i64.pow(i as u32 + 2);
+        }
+        self.code.push(Word::Value(instruction));
+        for operand in operands {
+            self.code.push(operand.word());
+        }
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!("__label_{}", self.labels)
+    }
+
+    fn place(&mut self, label:&str) {
+        self.symbols.insert(label.to_string(), self.code.len() as i64);
+    }
+
+    fn frame(&self, scale:i64, offset:i64) -> Word {
+        Word::Symbol(format!("__frame_{}", self.function), scale, offset)
+    }
+
+    fn allocate(&mut self) -> i64 {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        if self.next_slot > self.frame_size {
+            self.frame_size = self.next_slot;
+        }
+        slot
+    }
+
+    fn lookup(&self, name:&str, line:usize) -> Result<i64, String> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(&slot) = scope.get(name) {
+                return Ok(slot);
+            }
+        }
+        Err(format!("line {}: unknown variable {}", line, name))
+    }
+
+    fn copy(&mut self, from:Operand, to:Operand) {
+        self.emit(1, vec![from, Operand::Immediate(Word::Value(0)), to]);
+    }
+
+    fn jump(&mut self, label:&str) {
+        self.emit(5, vec![Operand::Immediate(Word::Value(1)), Operand::Immediate(Word::label(label))]);
+    }
+
+    fn jump_if_false(&mut self, condition:Operand, label:&str) {
+        self.emit(6, vec![condition, Operand::Immediate(Word::label(label))]);
+    }
+
+    fn function(&mut self, function:&Function) -> Result<(), String> {
+        self.function = function.name.clone();
+        self.place(&function.name);
+        self.source_map.functions.push((self.code.len(), function.name.clone()));
+        self.source_map.lines.push((self.code.len(), function.line));
+        let mut scope:HashMap<String, i64> = HashMap::new();
+        for (i, parameter) in function.parameters.iter().enumerate() {
+            scope.insert(parameter.clone(), i as i64 + 1);
+        }
+        self.scopes = vec![scope];
+        self.next_slot = function.parameters.len() as i64 + 1;
+        self.frame_size = self.next_slot;
+
+        self.block(&function.body)?;
+        self.statement(&Statement::Return(None))?;
+
+        let frame_size = self.frame_size;
+        self.symbols.insert(format!("__frame_{}", function.name), frame_size);
+        Ok(())
+    }
+
+    fn block(&mut self, statements:&Block) -> Result<(), String> {
+        let saved = self.next_slot;
+        self.scopes.push(HashMap::new());
+        for (line, statement) in statements {
+            self.source_map.lines.push((self.code.len(), *line));
+            self.statement(statement)?;
+        }
+        self.scopes.pop();
+        self.next_slot = saved;
+        Ok(())
+    }
+
+    fn statement(&mut self, statement:&Statement) -> Result<(), String> {
+        let saved = self.next_slot;
+        match statement {
+            Statement::Let(name, value) => {
+                let value = self.expression(value)?;
+                self.next_slot = saved;
+                let slot = self.allocate();
+                self.copy(value, Operand::slot(slot));
+                self.scopes.last_mut().unwrap().insert(name.clone(), slot);
+                return Ok(());
+            },
+            Statement::Assign(name, value, line) => {
+                let slot = self.lookup(name, *line)?;
+                let value = self.expression(value)?;
+                self.copy(value, Operand::slot(slot));
+            },
+            Statement::If(condition, then, otherwise) => {
+                let else_label = self.new_label();
+                let end_label = self.new_label();
+                let condition = self.expression(condition)?;
+                self.jump_if_false(condition, &else_label);
+                self.next_slot = saved;
+                self.block(then)?;
+                self.jump(&end_label);
+                self.place(&else_label);
+                self.block(otherwise)?;
+                self.place(&end_label);
+            },
+            Statement::While(condition, body) => {
+                let top_label = self.new_label();
+                let end_label = self.new_label();
+                self.place(&top_label);
+                let condition = self.expression(condition)?;
+                self.jump_if_false(condition, &end_label);
+                self.next_slot = saved;
+                self.block(body)?;
+                self.jump(&top_label);
+                self.place(&end_label);
+            },
+            Statement::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expression(value)?,
+                    None => Operand::Immediate(Word::Value(0)),
+                };
+                self.copy(value, Operand::Position(Word::label(RETURN_VALUE)));
+                self.emit(5, vec![Operand::Immediate(Word::Value(1)), Operand::slot(0)]);
+            },
+            Statement::Expression(value) => {
+                self.expression(value)?;
+            },
+        }
+        self.next_slot = saved;
+        Ok(())
+    }
+
+    fn expression(&mut self, expression:&Expression) -> Result<Operand, String> {
+        match expression {
+            Expression::Number(value) => Ok(Operand::Immediate(Word::Value(*value))),
+            Expression::Variable(name, line) => Ok(Operand::slot(self.lookup(name, *line)?)),
+            Expression::Negate(value) => {
+                let value = self.expression(value)?;
+                let result = self.allocate();
+                self.emit(2, vec![value, Operand::Immediate(Word::Value(-1)), Operand::slot(result)]);
+                Ok(Operand::slot(result))
+            },
+            Expression::Not(value) => {
+                let value = self.expression(value)?;
+                let result = self.allocate();
+                self.emit(8, vec![value, Operand::Immediate(Word::Value(0)), Operand::slot(result)]);
+                Ok(Operand::slot(result))
+            },
+            Expression::Binary(operator, left, right) if *operator == "&&" || *operator == "||" => {
+                // Short-circuit: the right side is only evaluated when the
+                // left side does not already decide the result.
+                let end_label = self.new_label();
+                let result = self.allocate();
+                let left_false = self.allocate();
+                let left = self.expression(left)?;
+                self.emit(8, vec![left, Operand::Immediate(Word::Value(0)), Operand::slot(left_false)]);
+                if *operator == "&&" {
+                    self.copy(Operand::Immediate(Word::Value(0)), Operand::slot(result));
+                    self.emit(5, vec![Operand::slot(left_false), Operand::Immediate(Word::label(&end_label))]);
+                } else {
+                    self.copy(Operand::Immediate(Word::Value(1)), Operand::slot(result));
+                    self.jump_if_false(Operand::slot(left_false), &end_label);
+                }
+                let right = self.expression(right)?;
+                self.emit(8, vec![right, Operand::Immediate(Word::Value(0)), Operand::slot(result)]);
+                self.emit(8, vec![Operand::slot(result), Operand::Immediate(Word::Value(0)), Operand::slot(result)]);
+                self.place(&end_label);
+                Ok(Operand::slot(result))
+            },
+            Expression::Binary(operator, left, right) => {
+                let left = self.expression(left)?;
+                let right = self.expression(right)?;
+                let result = self.allocate();
+                let target = Operand::slot(result);
+                match *operator {
+                    "+" => self.emit(1, vec![left, right, target]),
+                    "*" => self.emit(2, vec![left, right, target]),
+                    "-" => {
+                        self.emit(2, vec![right, Operand::Immediate(Word::Value(-1)), target.clone()]);
+                        self.emit(1, vec![left, target.clone(), target]);
+                    },
+                    "<" => self.emit(7, vec![left, right, target]),
+                    ">" => self.emit(7, vec![right, left, target]),
+                    "<=" => {
+                        self.emit(7, vec![right, left, target.clone()]);
+                        self.emit(8, vec![target.clone(), Operand::Immediate(Word::Value(0)), target]);
+                    },
+                    ">=" => {
+                        self.emit(7, vec![left, right, target.clone()]);
+                        self.emit(8, vec![target.clone(), Operand::Immediate(Word::Value(0)), target]);
+                    },
+                    "==" => self.emit(8, vec![left, right, target]),
+                    "!=" => {
+                        self.emit(8, vec![left, right, target.clone()]);
+                        self.emit(8, vec![target.clone(), Operand::Immediate(Word::Value(0)), target]);
+                    },
+                    _ => unreachable!(),
+                }
+                Ok(Operand::slot(result))
+            },
+            Expression::Call(name, arguments, line) => self.call(name, arguments, *line),
+        }
+    }
+
+    fn call(&mut self, name:&str, arguments:&Vec<Expression>, line:usize) -> Result<Operand, String> {
+        let arity = match name {
+            "input" => 0,
+            "output" => 1,
+            _ => match self.functions.get(name) {
+                Some(&arity) => arity,
+                None => return Err(format!("line {}: unknown function {}", line, name)),
+            },
+        };
+        if arguments.len() != arity {
+            return Err(format!("line {}: {} takes {} arguments but {} were given", line, name, arity, arguments.len()));
+        }
+
+        let mut values:Vec<Operand> = Vec::new();
+        for argument in arguments {
+            values.push(self.expression(argument)?);
+        }
+
+        match name {
+            "input" => {
+                let result = self.allocate();
+                self.emit(3, vec![Operand::slot(result)]);
+                Ok(Operand::slot(result))
+            },
+            "output" => {
+                self.emit(4, vec![values[0].clone()]);
+                Ok(Operand::Immediate(Word::Value(0)))
+            },
+            _ => {
+                let return_label = self.new_label();
+                for (i, value) in values.into_iter().enumerate() {
+                    let parameter = Operand::Relative(self.frame(1, i as i64 + 1));
+                    self.copy(value, parameter);
+                }
+                let return_address = Operand::Relative(self.frame(1, 0));
+                self.copy(Operand::Immediate(Word::label(&return_label)), return_address);
+                self.emit(9, vec![Operand::Immediate(self.frame(1, 0))]);
+                self.jump(name);
+                self.place(&return_label);
+                self.emit(9, vec![Operand::Immediate(self.frame(-1, 0))]);
+                let result = self.allocate();
+                self.copy(Operand::Position(Word::label(RETURN_VALUE)), Operand::slot(result));
+                Ok(Operand::slot(result))
+            },
+        }
+    }
+
+    fn resolve(&self) -> Vec<i64> {
+        self.code.iter().map(|word| match word {
+            Word::Value(value) => *value,
+            Word::Symbol(name, scale, offset) => scale * self.symbols[name] + offset,
+        }).collect()
+    }
+}
+
+pub fn compile(source:&str) -> Result<Vec<i64>, String> {
+    compile_with_symbols(source).map(|(program, _)| program)
+}
+
+// Compiles the program and records where the code of every function and
+// statement starts.
+pub fn compile_with_symbols(source:&str) -> Result<(Vec<i64>, Symbols), String> {
+    let mut parser = Parser { tokens: tokenize(source)?, position: 0 };
+    let functions = parser.program()?;
+
+    let mut generator = Generator {
+        code: Vec::new(),
+        symbols: HashMap::new(),
+        functions: HashMap::new(),
+        labels: 0,
+        function: String::new(),
+        scopes: Vec::new(),
+        next_slot: 0,
+        frame_size: 0,
+        source_map: Symbols::default(),
+    };
+    for function in &functions {
+        if generator.functions.insert(function.name.clone(), function.parameters.len()).is_some() {
+            return Err(format!("line {}: function {} is defined twice", function.line, function.name));
+        }
+        if function.name == "input" || function.name == "output" {
+            return Err(format!("line {}: {} is a builtin function", function.line, function.name));
+        }
+    }
+    match generator.functions.get("main") {
+        Some(0) => {},
+        Some(_) => return Err("main must not take any parameters".to_string()),
+        None => return Err("no main function".to_string()),
+    }
+
+    // Start: set up the first frame on the stack and call main, which
+    // returns to the halt instruction.
+    generator.emit(9, vec![Operand::Immediate(Word::label(STACK))]);
+    generator.copy(Operand::Immediate(Word::label(HALT)), Operand::slot(0));
+    generator.jump("main");
+    generator.place(HALT);
+    generator.emit(99, vec![]);
+
+    for function in &functions {
+        generator.function(function)?;
+    }
+
+    generator.place(RETURN_VALUE);
+    generator.code.push(Word::Value(0));
+    generator.place(STACK);
+
+    Ok((generator.resolve(), generator.source_map))
+}
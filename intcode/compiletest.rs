@@ -0,0 +1,194 @@
+use super::compiler;
+use super::vm::{Intcode, Status};
+
+// Programs for the compiler test suite: source, inputs and the outputs the
+// compiled program has to produce on the VM.
+const CASES:[(&str, &str, &[i64], &[i64]); 12] = [
+    ("echo", "
+        fn main() {
+            output(input());
+        }", &[42], &[42]),
+    ("arithmetic", "
+        fn main() {
+            let a = input();
+            let b = input();
+            output(a + b);
+            output(a - b);
+            output(a * b);
+            output(-a);
+            output(2 + 3 * 4 - (1 + 1));
+        }", &[7, 3], &[10, 4, 21, -7, 12]),
+    ("comparisons", "
+        fn main() {
+            let a = input();
+            let b = input();
+            output(a < b);
+            output(a <= b);
+            output(a > b);
+            output(a >= b);
+            output(a == b);
+            output(a != b);
+            output(!a);
+        }", &[3, 3], &[0, 1, 0, 1, 1, 0, 0]),
+    ("logic", "
+        fn main() {
+            output(1 && 5);
+            output(1 && 0);
+            output(0 || 0);
+            output(0 || 7);
+            output(1 < 2 && 2 < 3 || 0);
+        }", &[], &[1, 0, 0, 1, 1]),
+    ("short circuit", "
+        fn main() {
+            if (0 && noisy()) { output(1); }
+            if (1 || noisy()) { output(2); }
+        }
+
+        fn noisy() {
+            output(99);
+            return 1;
+        }", &[], &[2]),
+    ("if else", "
+        fn main() {
+            let n = input();
+            while (n != 0) {
+                if (n < 0) {
+                    output(-1);
+                } else if (n == 0) {
+                    output(0);
+                } else {
+                    output(1);
+                }
+                n = input();
+            }
+        }", &[-5, 8, 0], &[-1, 1]),
+    ("while", "
+        fn main() {
+            let n = input();
+            let sum = 0;
+            while (n > 0) {
+                sum = sum + n;
+                n = n - 1;
+            }
+            output(sum);
+        }", &[100], &[5050]),
+    ("scopes", "
+        fn main() {
+            let x = 1;
+            if (1) {
+                let x = 2;
+                let y = 3;
+                output(x + y);
+            }
+            let y = 10;
+            output(x + y);
+        }", &[], &[5, 11]),
+    ("functions", "
+        fn main() {
+            output(add3(1, 2, 3));
+            output(square(add3(1, 1, 1)) + square(2));
+            report(7);
+        }
+
+        fn add3(a, b, c) {
+            return a + b + c;
+        }
+
+        fn square(x) {
+            let result = x * x;
+            return result;
+        }
+
+        fn report(x) {
+            output(x);
+        }", &[], &[6, 13, 7]),
+    ("recursion", "
+        fn main() {
+            let n = input();
+            output(fib(n));
+            output(factorial(n));
+        }
+
+        fn fib(n) {
+            if (n < 2) {
+                return n;
+            }
+            return fib(n - 1) + fib(n - 2);
+        }
+
+        fn factorial(n) {
+            if (n <= 1) {
+                return 1;
+            }
+            return n * factorial(n - 1);
+        }", &[15], &[610, 1307674368000]),
+    ("mutual recursion", "
+        fn main() {
+            output(is_even(10));
+            output(is_even(7));
+        }
+
+        fn is_even(n) {
+            if (n == 0) { return 1; }
+            return is_odd(n - 1);
+        }
+
+        fn is_odd(n) {
+            if (n == 0) { return 0; }
+            return is_even(n - 1);
+        }", &[], &[1, 0]),
+    ("division by subtraction", "
+        // Intcode has no division, so it is done the slow way.
+        fn main() {
+            let a = input();
+            let b = input();
+            let q = 0;
+            while (a >= b) {
+                a = a - b;
+                q = q + 1;
+            }
+            output(q);
+            output(a);
+        }", &[47, 5], &[9, 2]),
+];
+
+const ERRORS:[(&str, &str, &str); 5] = [
+    ("unknown variable", "fn main() {\n    output(x);\n}", "line 2: unknown variable x"),
+    ("unknown function", "fn main() {\n    foo();\n}", "line 2: unknown function foo"),
+    ("arguments", "fn main() {\n    f(1);\n}\nfn f(a, b) {\n}", "line 2: f takes 2 arguments but 1 were given"),
+    ("syntax", "fn main() {\n    let = 3;\n}", "line 2: expected an identifier, found Symbol(\"=\")"),
+    ("no main", "fn start() {\n}", "no main function"),
+];
+
+pub fn run() -> bool {
+    let mut passed = 0;
+    let total = CASES.len() + ERRORS.len();
+
+    for (name, source, inputs, expected) in CASES.iter() {
+        match compiler::compile(source) {
+            Ok(program) => {
+                let mut computer = Intcode::new(program);
+                computer.append_input(&inputs.to_vec());
+                computer.execute();
+                let outputs:Vec<i64> = computer.outputs.iter().cloned().collect();
+                if computer.status == Status::Finished && outputs == expected.to_vec() {
+                    passed += 1;
+                } else {
+                    println!("{}: expected {:?}, got {:?} with status {:?}", name, expected, outputs, computer.status);
+                }
+            },
+            Err(error) => println!("{}: {}", name, error),
+        }
+    }
+
+    for (name, source, expected) in ERRORS.iter() {
+        match compiler::compile(source) {
+            Err(ref error) if error == expected => passed += 1,
+            Err(error) => println!("{}: expected error {:?}, got {:?}", name, expected, error),
+            Ok(_) => println!("{}: expected error {:?}, but it compiled", name, expected),
+        }
+    }
+
+    println!("{} of {} compiler tests passed", passed, total);
+    passed == total
+}
@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+use std::panic;
+
+use super::generate::{self, Features, Rng};
+use super::loader;
+use super::vm::Intcode;
+
+#[allow(dead_code)]
+#[path = "../day2/day2.rs"]
+mod day2;
+#[allow(dead_code)]
+#[path = "../day5/day5.rs"]
+mod day5;
+
+// What a run leaves behind: final status, outputs and the memory of the
+// image. The day 2 interpreter only reports address 0, so memory is compared
+// over the shorter of two runs.
+#[derive(Clone, Debug)]
+struct Outcome {
+    status: String,
+    outputs: Vec<i64>,
+    memory: Vec<i64>,
+}
+
+impl Outcome {
+    fn agrees(&self, other:&Outcome) -> bool {
+        let length = self.memory.len().min(other.memory.len());
+        self.status == other.status && self.outputs == other.outputs && self.memory[..length] == other.memory[..length]
+    }
+}
+
+enum Expected {
+    Agreement,
+    Outputs(Vec<i64>),
+    Memory(Vec<i64>),
+}
+
+struct Case {
+    name: String,
+    program: Vec<i64>,
+    inputs: Vec<i64>,
+    features: Features,
+    expected: Expected,
+}
+
+fn case(name:&str, program:&[i64], inputs:&[i64], features:Features, expected:Expected) -> Case {
+    Case { name: name.to_string(), program: program.to_vec(), inputs: inputs.to_vec(), features: features, expected: expected }
+}
+
+// The examples from the puzzle texts of days 2, 5 and 9.
+fn examples() -> Vec<Case> {
+    let compare = [
+        ("equal to 8, position mode", &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8][..], [0, 1, 0]),
+        ("less than 8, position mode", &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8][..], [1, 0, 0]),
+        ("equal to 8, immediate mode", &[3, 3, 1108, -1, 8, 3, 4, 3, 99][..], [0, 1, 0]),
+        ("less than 8, immediate mode", &[3, 3, 1107, -1, 8, 3, 4, 3, 99][..], [1, 0, 0]),
+    ];
+    let jumps = [
+        ("jump, position mode", &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9][..]),
+        ("jump, immediate mode", &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1][..]),
+    ];
+    let larger = [3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+                  1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99];
+    let quine = [109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+
+    let mut cases = vec![
+        case("day 2 example", &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[], Features::Arithmetic,
+             Expected::Memory(vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50])),
+        case("day 2 add", &[1, 0, 0, 0, 99], &[], Features::Arithmetic, Expected::Memory(vec![2, 0, 0, 0, 99])),
+        case("day 2 multiply", &[2, 3, 0, 3, 99], &[], Features::Arithmetic, Expected::Memory(vec![2, 3, 0, 6, 99])),
+        case("day 2 multiply past the halt", &[2, 4, 4, 5, 99, 0], &[], Features::Arithmetic, Expected::Memory(vec![2, 4, 4, 5, 99, 9801])),
+        case("day 2 rewritten halt", &[1, 1, 1, 4, 99, 5, 6, 0, 99], &[], Features::Arithmetic,
+             Expected::Memory(vec![30, 1, 1, 4, 2, 5, 6, 0, 99])),
+        case("day 5 echo", &[3, 0, 4, 0, 99], &[42], Features::Diagnostic, Expected::Outputs(vec![42])),
+        case("day 5 parameter modes", &[1002, 4, 3, 4, 33], &[], Features::Diagnostic, Expected::Memory(vec![1002, 4, 3, 4, 99])),
+        case("day 5 negative values", &[1101, 100, -1, 4, 0], &[], Features::Diagnostic, Expected::Memory(vec![1101, 100, -1, 4, 99])),
+        case("day 9 quine", &quine, &[], Features::Complete, Expected::Outputs(quine.to_vec())),
+        case("day 9 16 digit number", &[1102, 34915192, 34915192, 7, 4, 7, 99, 0], &[], Features::Complete,
+             Expected::Outputs(vec![1219070632396864])),
+        case("day 9 large number", &[104, 1125899906842624, 99], &[], Features::Complete, Expected::Outputs(vec![1125899906842624])),
+    ];
+    for (name, program, expected) in compare.iter() {
+        for (input, output) in [7, 8, 9].iter().zip(expected.iter()) {
+            cases.push(case(&format!("day 5 {} with {}", name, input), program, &[*input], Features::Diagnostic, Expected::Outputs(vec![*output])));
+        }
+    }
+    for (name, program) in jumps.iter() {
+        for input in [0, 5].iter() {
+            cases.push(case(&format!("day 5 {} with {}", name, input), program, &[*input], Features::Diagnostic,
+                            Expected::Outputs(vec![(*input != 0) as i64])));
+        }
+    }
+    for (input, output) in [(7, 999), (8, 1000), (9, 1001)].iter() {
+        cases.push(case(&format!("day 5 compare to 8 with {}", input), &larger, &[*input], Features::Diagnostic, Expected::Outputs(vec![*output])));
+    }
+    cases
+}
+
+// The puzzle inputs of the repository, where they are there to be read.
+fn puzzle_inputs() -> Vec<Case> {
+    let load = |path:&str| loader::load(path).ok();
+    let mut cases:Vec<Case> = Vec::new();
+    if let Some(mut program) = load("../day2/input.txt") {
+        program[1] = 12;
+        program[2] = 2;
+        cases.push(case("day 2 input", &program, &[], Features::Arithmetic, Expected::Agreement));
+    }
+    if let Some(program) = load("../day5/input.txt") {
+        cases.push(case("day 5 input, system 1", &program, &[1], Features::Diagnostic, Expected::Agreement));
+        cases.push(case("day 5 input, system 5", &program, &[5], Features::Diagnostic, Expected::Agreement));
+    }
+    if let Some(program) = load("../day9/input.txt") {
+        cases.push(case("day 9 input, test mode", &program, &[1], Features::Complete, Expected::Agreement));
+    }
+    cases
+}
+
+fn run_vm(program:&Vec<i64>, inputs:&Vec<i64>, decode_cache:bool) -> Outcome {
+    let mut computer = Intcode::new(program.clone());
+    computer.set_decode_cache(decode_cache);
+    computer.append_input(inputs);
+    computer.execute();
+    Outcome {
+        status: format!("{:?}", computer.status),
+        outputs: computer.outputs.iter().cloned().collect(),
+        memory: (0..program.len()).map(|a| computer.memory.get(&a).cloned().unwrap_or(0)).collect(),
+    }
+}
+
+fn narrow(values:&Vec<i64>) -> Vec<i32> {
+    values.iter().map(|&v| v as i32).collect()
+}
+
+fn widen(values:&VecDeque<i32>) -> Vec<i64> {
+    values.iter().map(|&v| v as i64).collect()
+}
+
+fn run_day2(program:&Vec<i64>) -> Outcome {
+    let value = day2::intcode(narrow(program), program[1] as i32, program[2] as i32);
+    Outcome { status: "Finished".to_string(), outputs: Vec::new(), memory: vec![value as i64] }
+}
+
+fn run_day5(program:&Vec<i64>, inputs:&Vec<i64>) -> Outcome {
+    let mut computer = day5::Intcode::new(narrow(program));
+    for &input in inputs {
+        computer.input(input as i32);
+    }
+    computer.execute();
+    Outcome {
+        status: format!("{:?}", computer.status),
+        outputs: widen(&computer.outputs),
+        memory: computer.memory.iter().map(|&v| v as i64).collect(),
+    }
+}
+
+fn fits_i32(values:&Vec<i64>) -> bool {
+    values.iter().all(|&v| v >= i32::min_value() as i64 && v <= i32::max_value() as i64)
+}
+
+// Runs the case on every interpreter that implements its features. The
+// 32-bit interpreters are left out when any value leaves their range, since
+// they would overflow where the reference does not.
+fn check(case:&Case) -> Result<usize, String> {
+    let reference = run_vm(&case.program, &case.inputs, false);
+    let mut outcomes:Vec<(&str, Outcome)> = vec![("vm", reference.clone())];
+    let mut run = |name:&'static str, implementation:&dyn Fn() -> Outcome| {
+        let outcome = panic::catch_unwind(panic::AssertUnwindSafe(|| implementation())).unwrap_or_else(|_| Outcome {
+            status: "Panicked".to_string(),
+            outputs: Vec::new(),
+            memory: Vec::new(),
+        });
+        outcomes.push((name, outcome));
+    };
+    run("vm with decode cache", &|| run_vm(&case.program, &case.inputs, true));
+    let narrow = case.features != Features::Complete && fits_i32(&case.program) && fits_i32(&case.inputs)
+                 && fits_i32(&reference.outputs) && fits_i32(&reference.memory);
+    if narrow {
+        run("day 5", &|| run_day5(&case.program, &case.inputs));
+    }
+    if narrow && case.features == Features::Arithmetic {
+        run("day 2", &|| run_day2(&case.program));
+    }
+
+    let mut problems:Vec<String> = Vec::new();
+    match &case.expected {
+        Expected::Outputs(outputs) if *outputs != reference.outputs => problems.push(format!("expected outputs {:?}", outputs)),
+        Expected::Memory(memory) if *memory != reference.memory => problems.push(format!("expected memory {:?}", memory)),
+        _ => {},
+    }
+    if !outcomes.iter().all(|(_, outcome)| outcome.agrees(&reference)) {
+        problems.push("interpreters disagree".to_string());
+    }
+    if problems.is_empty() {
+        return Ok(outcomes.len());
+    }
+    let mut message = format!("{}: {}\n  program {:?}\n  inputs {:?}", case.name, problems.join(", "), case.program, case.inputs);
+    for (name, outcome) in &outcomes {
+        message.push_str(&format!("\n  {:<21} {} outputs {:?} memory {:?}", name, outcome.status, outcome.outputs, outcome.memory));
+    }
+    Err(message)
+}
+
+// Checks the puzzle examples, the repository's inputs and `count` random
+// programs generated from `seed` on every interpreter in the repository.
+pub fn run(count:usize, seed:u64) -> bool {
+    let mut cases = examples();
+    cases.extend(puzzle_inputs());
+    let fixed = cases.len();
+    let mut rng = Rng::new(seed);
+    let features = [Features::Arithmetic, Features::Diagnostic, Features::Complete];
+    for i in 0..count {
+        let (program, inputs) = generate::program(&mut rng, features[i % features.len()]);
+        cases.push(Case { name: format!("random program {} (seed {})", i, seed), program: program, inputs: inputs, features: features[i % features.len()], expected: Expected::Agreement });
+    }
+
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut passed = 0;
+    let mut runs = 0;
+    for case in &cases {
+        match check(case) {
+            Ok(interpreters) => {
+                passed += 1;
+                runs += interpreters;
+            },
+            Err(message) => println!("{}", message),
+        }
+    }
+    panic::set_hook(hook);
+
+    println!("{} of {} cases agree ({} examples and inputs, {} random), {} interpreter runs", passed, cases.len(), fixed, count, runs);
+    passed == cases.len()
+}
@@ -0,0 +1,678 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use super::compiler::{self, Symbols};
+use super::json::{object, string, Json};
+use super::loader;
+use super::vm::{Intcode, Status};
+
+// Variable references: the fixed scopes, the input and output queues, and
+// one reference per hundred words of memory from MEMORY_CHUNKS on.
+const REGISTERS:i64 = 1;
+const QUEUES:i64 = 2;
+const MEMORY:i64 = 3;
+const INPUTS:i64 = 4;
+const OUTPUTS:i64 = 5;
+const MEMORY_CHUNKS:i64 = 1000;
+const CHUNK:usize = 100;
+
+const THREAD:i64 = 1;
+
+// Programs with this extension are compiled from source; any other file is
+// an Intcode image.
+const SOURCE_EXTENSION:&str = "src";
+
+// Larger bodies are taken for a corrupt header rather than allocated.
+const MAX_MESSAGE:usize = 1 << 24;
+
+// Reads the next message, or None at the end of the stream. A message
+// without a usable Content-Length or with a body that is not JSON is an
+// error, and reading can go on after it: the header of the next message is
+// found even when it follows a body on the same line.
+fn read_message<R:BufRead>(reader:&mut R) -> Option<Result<Json, String>> {
+    let mut length:Option<Result<usize, String>> = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            if length.is_none() {
+                return Some(Err("message without a Content-Length header".to_string()));
+            }
+            break;
+        }
+        if let Some(start) = line.to_ascii_lowercase().find("content-length:") {
+            let value = line[start + 15..].trim();
+            length = Some(value.parse().ok().filter(|&n| n <= MAX_MESSAGE).ok_or(format!("bad Content-Length {:?}", value)));
+        }
+    }
+    let length = match length? {
+        Ok(length) => length,
+        Err(error) => return Some(Err(error)),
+    };
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).ok()?;
+    Some(Json::parse(&String::from_utf8_lossy(&body)).map_err(|error| format!("body is not JSON: {}", error)))
+}
+
+fn write_message<W:Write>(writer:&mut W, message:&Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn parse_inputs(value:&Json) -> Result<Vec<i64>, String> {
+    match value {
+        Json::Null => Ok(Vec::new()),
+        Json::String(text) => text.split(',').filter(|v| !v.trim().is_empty())
+                                  .map(|v| v.trim().parse().map_err(|_| format!("bad input {:?}", v))).collect(),
+        Json::Array(values) => values.iter().map(|v| v.as_i64().ok_or(format!("bad input {}", v))).collect(),
+        _ => Err("inputs are a list of numbers".to_string()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Continue,
+    Instruction,
+    // Stop at the start of another line in the same or a calling frame.
+    Over,
+    // Stop at the start of any other line.
+    In,
+    // Stop once the relative base drops below the current frame.
+    Out,
+}
+
+pub struct Adapter<W:Write> {
+    messages: Receiver<Result<Json, String>>,
+    pending: VecDeque<Result<Json, String>>,
+    writer: W,
+    sequence: i64,
+    computer: Option<Intcode>,
+    outputs: Vec<i64>,
+    symbols: Symbols,
+    source: Option<String>,
+    line_breakpoints: BTreeSet<usize>,
+    instruction_breakpoints: BTreeSet<usize>,
+    stop_on_entry: bool,
+}
+
+impl<W:Write> Adapter<W> {
+    // Serves one debugging session over the given streams. Messages are read
+    // on their own thread so that a pause request reaches a running program.
+    pub fn serve<R:Read + Send + 'static>(reader:R, writer:W) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Some(message) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut adapter = Adapter {
+            messages: receiver,
+            pending: VecDeque::new(),
+            writer: writer,
+            sequence: 0,
+            computer: None,
+            outputs: Vec::new(),
+            symbols: Symbols::default(),
+            source: None,
+            line_breakpoints: BTreeSet::new(),
+            instruction_breakpoints: BTreeSet::new(),
+            stop_on_entry: false,
+        };
+        loop {
+            let message = match adapter.pending.pop_front() {
+                Some(message) => message,
+                None => match adapter.messages.recv() {
+                    Ok(message) => message,
+                    Err(_) => return Ok(()),
+                },
+            };
+            match message {
+                Ok(message) => if message.get("type").as_str() == Some("request") && adapter.handle(&message)? {
+                    return Ok(());
+                },
+                // Without a request there is nothing to respond to, so the
+                // client hears of it on the console.
+                Err(error) => adapter.event("output", object(vec![
+                    ("category", string("stderr")),
+                    ("output", Json::String(format!("Ignored a malformed message: {}\n", error))),
+                ]))?,
+            }
+        }
+    }
+
+    fn send(&mut self, mut fields:Vec<(&str, Json)>) -> io::Result<()> {
+        self.sequence += 1;
+        fields.insert(0, ("seq", Json::Number(self.sequence)));
+        write_message(&mut self.writer, &object(fields))
+    }
+
+    fn event(&mut self, name:&str, body:Json) -> io::Result<()> {
+        self.send(vec![("type", string("event")), ("event", string(name)), ("body", body)])
+    }
+
+    fn respond(&mut self, request:&Json, result:Result<Json, String>) -> io::Result<()> {
+        let mut fields = vec![
+            ("type", string("response")),
+            ("request_seq", request.get("seq").clone()),
+            ("command", request.get("command").clone()),
+            ("success", Json::Bool(result.is_ok())),
+        ];
+        match result {
+            Ok(body) => fields.push(("body", body)),
+            Err(message) => fields.push(("message", Json::String(message))),
+        }
+        self.send(fields)
+    }
+
+    fn stopped(&mut self, reason:&str, description:&str) -> io::Result<()> {
+        self.event("stopped", object(vec![
+            ("reason", string(reason)),
+            ("description", string(description)),
+            ("threadId", Json::Number(THREAD)),
+            ("allThreadsStopped", Json::Bool(true)),
+        ]))
+    }
+
+    // Handles one request, returning whether the session is over.
+    fn handle(&mut self, request:&Json) -> io::Result<bool> {
+        let arguments = request.get("arguments");
+        let command = request.get("command").as_str().unwrap_or("").to_string();
+        if self.computer.is_none() && !["initialize", "launch", "disconnect", "terminate"].contains(&command.as_str()) {
+            self.respond(request, Err("No program launched".to_string()))?;
+            return Ok(false);
+        }
+        match command.as_str() {
+            "initialize" => {
+                self.respond(request, Ok(object(vec![
+                    ("supportsConfigurationDoneRequest", Json::Bool(true)),
+                    ("supportsInstructionBreakpoints", Json::Bool(true)),
+                    ("supportsSteppingGranularity", Json::Bool(true)),
+                    ("supportsTerminateRequest", Json::Bool(true)),
+                ])))?;
+                self.event("initialized", object(vec![]))?;
+            },
+            "launch" => {
+                let result = self.launch(arguments);
+                self.respond(request, result.map(|_| object(vec![])))?;
+            },
+            "setBreakpoints" => {
+                let lines:Vec<usize> = arguments.get("breakpoints").as_array().iter()
+                                                .filter_map(|b| b.get("line").as_i64()).map(|l| l as usize).collect();
+                self.line_breakpoints.clear();
+                let mut breakpoints:Vec<Json> = Vec::new();
+                for line in lines {
+                    match self.symbols.address_of(line) {
+                        Some((address, line)) => {
+                            self.line_breakpoints.insert(address);
+                            breakpoints.push(object(vec![
+                                ("verified", Json::Bool(true)),
+                                ("line", Json::Number(line as i64)),
+                                ("instructionReference", Json::String(address.to_string())),
+                            ]));
+                        },
+                        None => breakpoints.push(object(vec![
+                            ("verified", Json::Bool(false)),
+                            ("line", Json::Number(line as i64)),
+                            ("message", string("No code at or after this line in the symbol map")),
+                        ])),
+                    }
+                }
+                self.respond(request, Ok(object(vec![("breakpoints", Json::Array(breakpoints))])))?;
+            },
+            "setInstructionBreakpoints" => {
+                self.instruction_breakpoints.clear();
+                let mut breakpoints:Vec<Json> = Vec::new();
+                for breakpoint in arguments.get("breakpoints").as_array() {
+                    let address = breakpoint.get("instructionReference").as_str().and_then(|r| r.parse::<i64>().ok())
+                                            .map(|a| a + breakpoint.get("offset").as_i64().unwrap_or(0));
+                    match address {
+                        Some(address) if address >= 0 => {
+                            self.instruction_breakpoints.insert(address as usize);
+                            breakpoints.push(object(vec![("verified", Json::Bool(true)), ("instructionReference", Json::String(address.to_string()))]));
+                        },
+                        _ => breakpoints.push(object(vec![("verified", Json::Bool(false)), ("message", string("Not an address"))])),
+                    }
+                }
+                self.respond(request, Ok(object(vec![("breakpoints", Json::Array(breakpoints))])))?;
+            },
+            "configurationDone" => {
+                self.respond(request, Ok(object(vec![])))?;
+                if self.stop_on_entry {
+                    self.stopped("entry", "Stopped at the first instruction")?;
+                } else {
+                    self.resume(Step::Continue)?;
+                }
+            },
+            "threads" => {
+                let thread = object(vec![("id", Json::Number(THREAD)), ("name", string("Intcode"))]);
+                self.respond(request, Ok(object(vec![("threads", Json::Array(vec![thread]))])))?;
+            },
+            "stackTrace" => {
+                let frame = self.frame();
+                self.respond(request, Ok(object(vec![("stackFrames", Json::Array(vec![frame])), ("totalFrames", Json::Number(1))])))?;
+            },
+            "scopes" => {
+                let scope = |name:&str, reference:i64, expensive:bool| object(vec![
+                    ("name", string(name)),
+                    ("variablesReference", Json::Number(reference)),
+                    ("expensive", Json::Bool(expensive)),
+                ]);
+                let scopes = vec![scope("Registers", REGISTERS, false), scope("I/O", QUEUES, false), scope("Memory", MEMORY, true)];
+                self.respond(request, Ok(object(vec![("scopes", Json::Array(scopes))])))?;
+            },
+            "variables" => {
+                let variables = self.variables(arguments.get("variablesReference").as_i64().unwrap_or(0));
+                self.respond(request, Ok(object(vec![("variables", Json::Array(variables))])))?;
+            },
+            "evaluate" => {
+                let result = self.evaluate(arguments.get("expression").as_str().unwrap_or(""));
+                self.respond(request, result.map(|text| object(vec![("result", Json::String(text)), ("variablesReference", Json::Number(0))])))?;
+            },
+            "continue" => {
+                self.respond(request, Ok(object(vec![("allThreadsContinued", Json::Bool(true))])))?;
+                self.resume(Step::Continue)?;
+            },
+            "next" | "stepIn" | "stepOut" => {
+                self.respond(request, Ok(object(vec![])))?;
+                let instruction = arguments.get("granularity").as_str() == Some("instruction") || self.symbols.lines.is_empty();
+                let step = match command.as_str() {
+                    "stepOut" => Step::Out,
+                    _ if instruction => Step::Instruction,
+                    "next" => Step::Over,
+                    _ => Step::In,
+                };
+                self.resume(step)?;
+            },
+            "pause" => {
+                self.respond(request, Ok(object(vec![])))?;
+                self.stopped("pause", "Paused")?;
+            },
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(object(vec![])))?;
+                if command == "terminate" {
+                    self.event("terminated", object(vec![]))?;
+                }
+                return Ok(true);
+            },
+            _ => self.respond(request, Err(format!("Unsupported request {}", command)))?,
+        }
+        Ok(false)
+    }
+
+    // The program is either an Intcode program, as text or a binary image,
+    // with an optional symbol map and source path, or source code that is
+    // compiled on the spot.
+    fn launch(&mut self, arguments:&Json) -> Result<(), String> {
+        let path = arguments.get("program").as_str().ok_or("launch needs a program")?;
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let text = String::from_utf8_lossy(&data).to_string();
+        let program = if Path::new(path).extension().map_or(false, |e| e == SOURCE_EXTENSION) {
+            let (program, symbols) = compiler::compile_with_symbols(&text).map_err(|e| format!("{}: {}", path, e))?;
+            self.symbols = symbols;
+            self.source = Some(path.to_string());
+            program
+        } else {
+            let program = if data.starts_with(loader::MAGIC) {
+                loader::from_image(&data).map_err(|e| format!("{}: {}", path, e))?
+            } else {
+                loader::parse(&text).map_err(|e| format!("{}: {}", path, e))?
+            };
+            if let Some(map) = arguments.get("symbols").as_str() {
+                let text = fs::read_to_string(map).map_err(|e| format!("{}: {}", map, e))?;
+                self.symbols = Symbols::from_text(&text).map_err(|e| format!("{}: {}", map, e))?;
+            }
+            self.source = arguments.get("source").as_str().map(|s| s.to_string());
+            program
+        };
+        let mut computer = Intcode::new(program);
+        computer.append_input(&parse_inputs(arguments.get("inputs"))?);
+        self.computer = Some(computer);
+        self.stop_on_entry = arguments.get("stopOnEntry").as_bool().unwrap_or(false);
+        Ok(())
+    }
+
+    fn frame(&self) -> Json {
+        let computer = self.computer.as_ref().unwrap();
+        let pc = computer.program_counter;
+        let name = match self.symbols.function_at(pc) {
+            Some(function) => function.to_string(),
+            None => format!("pc {}", pc),
+        };
+        let mut fields = vec![
+            ("id", Json::Number(0)),
+            ("name", Json::String(name)),
+            ("line", Json::Number(self.symbols.line_at(pc).unwrap_or(0) as i64)),
+            ("column", Json::Number(1)),
+            ("instructionPointerReference", Json::String(pc.to_string())),
+        ];
+        if let Some(source) = &self.source {
+            fields.push(("source", object(vec![("path", Json::String(source.clone()))])));
+        }
+        object(fields)
+    }
+
+    fn variables(&self, reference:i64) -> Vec<Json> {
+        let computer = self.computer.as_ref().unwrap();
+        let variable = |name:String, value:String, reference:i64| object(vec![
+            ("name", Json::String(name)),
+            ("value", Json::String(value)),
+            ("variablesReference", Json::Number(reference)),
+        ]);
+        let list = |values:&mut dyn Iterator<Item = &i64>| -> Vec<Json> {
+            values.enumerate().map(|(i, v)| variable(format!("[{}]", i), v.to_string(), 0)).collect()
+        };
+        match reference {
+            REGISTERS => {
+                let mut variables = vec![
+                    variable("program_counter".to_string(), computer.program_counter.to_string(), 0),
+                    variable("relative_base".to_string(), computer.relative_base.to_string(), 0),
+                    variable("status".to_string(), format!("{:?}", computer.status), 0),
+                ];
+                if let Some(fault) = computer.fault() {
+                    variables.push(variable("fault".to_string(), format!("{:?}", fault.kind), 0));
+                }
+                variables
+            },
+            QUEUES => vec![
+                variable("inputs".to_string(), format!("{} queued", computer.inputs.len()), INPUTS),
+                variable("outputs".to_string(), format!("{} produced", self.outputs.len()), OUTPUTS),
+            ],
+            INPUTS => list(&mut computer.inputs.iter()),
+            OUTPUTS => list(&mut self.outputs.iter()),
+            MEMORY => {
+                let chunks:BTreeSet<usize> = computer.memory.keys().map(|a| a / CHUNK).collect();
+                chunks.iter().map(|c| {
+                    let start = c * CHUNK;
+                    variable(format!("{}..{}", start, start.saturating_add(CHUNK - 1)), String::new(), MEMORY_CHUNKS.saturating_add(*c as i64))
+                }).collect()
+            },
+            // The reference comes from the client, so a chunk past the end of
+            // memory is empty rather than an overflow.
+            _ if reference >= MEMORY_CHUNKS => match ((reference - MEMORY_CHUNKS) as usize).checked_mul(CHUNK) {
+                Some(start) => (start..=start.saturating_add(CHUNK - 1))
+                    .filter_map(|a| computer.memory.get(&a).map(|v| variable(format!("[{}]", a), v.to_string(), 0))).collect(),
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    // The debug console takes `input <values>` to feed a waiting program,
+    // an address such as `12` or `[12]` for a memory word, and the register
+    // names.
+    fn evaluate(&mut self, expression:&str) -> Result<String, String> {
+        let computer = self.computer.as_mut().unwrap();
+        let expression = expression.trim();
+        if expression.starts_with("input ") {
+            let values = parse_inputs(&Json::String(expression[6..].to_string()))?;
+            computer.append_input(&values);
+            return Ok(format!("{} inputs queued", computer.inputs.len()));
+        }
+        match expression {
+            "pc" | "program_counter" => return Ok(computer.program_counter.to_string()),
+            "rb" | "relative_base" => return Ok(computer.relative_base.to_string()),
+            _ => {},
+        }
+        match expression.trim_start_matches('[').trim_end_matches(']').parse::<usize>() {
+            Ok(address) => Ok(computer.read(address).to_string()),
+            Err(_) => Err(format!("Expected input <values>, an address or a register, found {:?}", expression)),
+        }
+    }
+
+    // Runs until a breakpoint, the end of the step, a pause request or the
+    // program stopping on its own. Requests other than pause wait until the
+    // program has stopped.
+    fn resume(&mut self, step:Step) -> io::Result<()> {
+        let computer = self.computer.as_mut().unwrap();
+        if computer.status == Status::Blocked && !computer.inputs.is_empty() {
+            computer.status = Status::Running;
+        }
+        let start_line = self.symbols.line_at(computer.program_counter);
+        let start_base = computer.relative_base;
+        let mut ticks:u64 = 0;
+        let mut paused = false;
+        loop {
+            let computer = self.computer.as_mut().unwrap();
+            if computer.status != Status::Running {
+                break;
+            }
+            computer.tick();
+            ticks += 1;
+            let outputs:Vec<i64> = computer.outputs.drain(..).collect();
+            let pc = computer.program_counter;
+            let base = computer.relative_base;
+            let running = computer.status == Status::Running;
+            for output in outputs {
+                self.outputs.push(output);
+                self.event("output", object(vec![("category", string("stdout")), ("output", Json::String(format!("{}\n", output)))]))?;
+            }
+            if !running {
+                break;
+            }
+            if self.line_breakpoints.contains(&pc) || self.instruction_breakpoints.contains(&pc) {
+                return self.stopped("breakpoint", "Breakpoint");
+            }
+            let line_start = self.symbols.lines.iter().any(|&(address, _)| address == pc);
+            let other_line = line_start && self.symbols.line_at(pc) != start_line;
+            let done = match step {
+                Step::Continue => false,
+                Step::Instruction => true,
+                Step::Over => other_line && base <= start_base,
+                Step::In => other_line,
+                Step::Out => base < start_base,
+            };
+            if done {
+                return self.stopped("step", "Step");
+            }
+            if ticks % 1024 == 0 {
+                while let Ok(message) = self.messages.try_recv() {
+                    match message {
+                        Ok(ref request) if request.get("command").as_str() == Some("pause") => {
+                            self.respond(request, Ok(object(vec![])))?;
+                            paused = true;
+                        },
+                        _ => self.pending.push_back(message),
+                    }
+                }
+                if paused {
+                    return self.stopped("pause", "Paused");
+                }
+            }
+        }
+        let computer = self.computer.as_ref().unwrap();
+        match computer.status {
+            Status::Finished => {
+                self.event("exited", object(vec![("exitCode", Json::Number(0))]))?;
+                self.event("terminated", object(vec![]))
+            },
+            Status::Blocked => self.stopped("pause", "Waiting for input: evaluate input <values> in the debug console"),
+            _ => {
+                let description = format!("Fault: {:?}", computer.fault().map(|f| f.kind));
+                self.stopped("exception", &description)
+            },
+        }
+    }
+}
+
+pub fn serve_stdio() -> io::Result<()> {
+    Adapter::serve(io::stdin(), io::stdout())
+}
+
+// Stands in for an editor: sends requests and collects the responses and
+// events that come back.
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    sequence: i64,
+    events: VecDeque<Json>,
+    output: String,
+}
+
+impl Client {
+    fn receive(&mut self) -> Json {
+        let message = match read_message(&mut self.reader) {
+            Some(Ok(message)) => message,
+            Some(Err(error)) => panic!("Malformed message from the adapter: {}", error),
+            None => panic!("Adapter hung up"),
+        };
+        if message.get("event").as_str() == Some("output") && message.get("body").get("category").as_str() == Some("stdout") {
+            self.output.push_str(message.get("body").get("output").as_str().unwrap_or(""));
+        }
+        message
+    }
+
+    fn request(&mut self, command:&str, arguments:Json) -> Json {
+        self.sequence += 1;
+        let request = object(vec![
+            ("seq", Json::Number(self.sequence)),
+            ("type", string("request")),
+            ("command", string(command)),
+            ("arguments", arguments),
+        ]);
+        write_message(&mut self.writer, &request).unwrap();
+        loop {
+            let message = self.receive();
+            if message.get("type").as_str() == Some("response") && message.get("request_seq").as_i64() == Some(self.sequence) {
+                return message;
+            }
+            self.events.push_back(message);
+        }
+    }
+
+    fn wait(&mut self, event:&str) -> Json {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.receive(),
+            };
+            if message.get("event").as_str() == Some(event) {
+                return message.get("body").clone();
+            }
+        }
+    }
+
+    fn location(&mut self) -> (String, i64) {
+        let trace = self.request("stackTrace", object(vec![("threadId", Json::Number(THREAD))]));
+        let frame = trace.get("body").get("stackFrames").as_array()[0].clone();
+        (frame.get("name").as_str().unwrap_or("").to_string(), frame.get("line").as_i64().unwrap_or(0))
+    }
+}
+
+const SELFTEST_SOURCE:&str = "fn main() {
+    let n = input();
+    let total = 0;
+    while (n > 0) {
+        total = total + square(n);
+        n = n - 1;
+    }
+    output(total);
+}
+
+fn square(x) {
+    return x * x;
+}
+";
+
+// Debugs a compiled program through a local socket: line and instruction
+// breakpoints, stepping in, out and over, and the variables and console.
+pub fn selftest() -> bool {
+    let path = env::temp_dir().join("intcode-daptest.src");
+    fs::write(&path, SELFTEST_SOURCE).expect("Unable to write file");
+    let broken = env::temp_dir().join("intcode-daptest.txt");
+    fs::write(&broken, "1,0,0,0,\n99,x\n").expect("Unable to write file");
+    let listener = TcpListener::bind(("127.0.0.1", 0)).expect("Unable to listen");
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        Adapter::serve(stream.try_clone().unwrap(), stream).unwrap();
+    });
+    let stream = TcpStream::connect(("127.0.0.1", port)).expect("Unable to connect");
+    let mut client = Client { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream, sequence: 0, events: VecDeque::new(), output: String::new() };
+    let path = path.to_string_lossy().to_string();
+    let source = object(vec![("path", Json::String(path.clone()))]);
+    let lines = |lines:&[i64]| Json::Array(lines.iter().map(|&l| object(vec![("line", Json::Number(l))])).collect());
+
+    let mut checks:Vec<(&str, bool)> = Vec::new();
+    let initialize = client.request("initialize", object(vec![("adapterID", string("intcode"))]));
+    checks.push(("initialize", initialize.get("body").get("supportsInstructionBreakpoints").as_bool() == Some(true)));
+    client.wait("initialized");
+    // A body that is not JSON, then one with a broken length: both are
+    // reported and the session goes on.
+    client.writer.write_all(b"Content-Length: 5\r\n\r\n{oops").unwrap();
+    client.writer.write_all(b"Content-Length: x\r\n\r\n").unwrap();
+    let malformed = (client.wait("output"), client.wait("output"));
+    checks.push(("malformed messages reported", malformed.0.get("category").as_str() == Some("stderr") && malformed.1.get("category").as_str() == Some("stderr")));
+    let rejected = client.request("launch", object(vec![("program", Json::String(broken.to_string_lossy().to_string()))]));
+    checks.push(("Intcode parse error reported", rejected.get("message").as_str().map_or(false, |m| m.contains("line 2, column 4"))));
+    let launch = client.request("launch", object(vec![("program", Json::String(path.clone())), ("inputs", string("3"))]));
+    checks.push(("launch", launch.get("success").as_bool() == Some(true)));
+    let set = client.request("setBreakpoints", object(vec![("source", source.clone()), ("breakpoints", lines(&[5, 10]))]));
+    let verified:Vec<(Option<bool>, Option<i64>)> = set.get("body").get("breakpoints").as_array().iter()
+                                                       .map(|b| (b.get("verified").as_bool(), b.get("line").as_i64())).collect();
+    checks.push(("line breakpoints moved to code", verified == vec![(Some(true), Some(5)), (Some(true), Some(11))]));
+    client.request("setBreakpoints", object(vec![("source", source.clone()), ("breakpoints", lines(&[5]))]));
+    client.request("configurationDone", object(vec![]));
+    checks.push(("breakpoint hit", client.wait("stopped").get("reason").as_str() == Some("breakpoint")));
+    checks.push(("stopped in main on line 5", client.location() == ("main".to_string(), 5)));
+
+    let registers = client.request("variables", object(vec![("variablesReference", Json::Number(REGISTERS))]));
+    let names:Vec<String> = registers.get("body").get("variables").as_array().iter().map(|v| v.get("name").as_str().unwrap_or("").to_string()).collect();
+    checks.push(("registers", names == vec!["program_counter", "relative_base", "status"]));
+    let queues = client.request("variables", object(vec![("variablesReference", Json::Number(QUEUES))]));
+    checks.push(("input consumed", queues.get("body").get("variables").as_array()[0].get("value").as_str() == Some("0 queued")));
+    let word = client.request("evaluate", object(vec![("expression", string("[0]"))]));
+    checks.push(("evaluate memory", word.get("body").get("result").as_str() == Some("109")));
+    let far = client.request("variables", object(vec![("variablesReference", Json::Number(i64::MAX))]));
+    checks.push(("memory chunk past the end", far.get("success").as_bool() == Some(true) && far.get("body").get("variables").as_array().is_empty()));
+
+    client.request("stepIn", object(vec![("threadId", Json::Number(THREAD))]));
+    client.wait("stopped");
+    checks.push(("step into square", client.location() == ("square".to_string(), 12)));
+    client.request("stepOut", object(vec![("threadId", Json::Number(THREAD))]));
+    client.wait("stopped");
+    checks.push(("step out to main", client.location() == ("main".to_string(), 5)));
+    client.request("next", object(vec![("threadId", Json::Number(THREAD))]));
+    client.wait("stopped");
+    checks.push(("step over to line 6", client.location() == ("main".to_string(), 6)));
+    client.request("next", object(vec![("threadId", Json::Number(THREAD)), ("granularity", string("instruction"))]));
+    client.wait("stopped");
+    let (_, line) = client.location();
+    checks.push(("instruction step stays on line 6", line == 6));
+
+    client.request("setBreakpoints", object(vec![("source", source.clone()), ("breakpoints", lines(&[]))]));
+    let halt = client.request("setInstructionBreakpoints", object(vec![("breakpoints", Json::Array(vec![object(vec![("instructionReference", string("9"))])]))]));
+    checks.push(("instruction breakpoint", halt.get("body").get("breakpoints").as_array()[0].get("verified").as_bool() == Some(true)));
+    client.request("continue", object(vec![("threadId", Json::Number(THREAD))]));
+    client.wait("stopped");
+    checks.push(("stopped at the halt", client.location().0 == "pc 9"));
+    client.request("continue", object(vec![("threadId", Json::Number(THREAD))]));
+    checks.push(("exited", client.wait("exited").get("exitCode").as_i64() == Some(0)));
+    checks.push(("output", client.output == "14\n"));
+    client.request("disconnect", object(vec![]));
+    server.join().unwrap();
+    fs::remove_file(&path).ok();
+    fs::remove_file(&broken).ok();
+
+    let passed = checks.iter().filter(|(_, ok)| *ok).count();
+    for (name, ok) in &checks {
+        if !ok {
+            println!("{}: failed", name);
+        }
+    }
+    println!("{} of {} adapter checks passed", passed, checks.len());
+    passed == checks.len()
+}
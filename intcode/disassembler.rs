@@ -0,0 +1,180 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+
+use super::vm::{Coverage, Instruction, Intcode, Opcode, Parameter};
+
+pub fn mnemonic(opcode:Opcode) -> &'static str {
+    match opcode {
+        Opcode::Add => "add",
+        Opcode::Multiply => "mul",
+        Opcode::Input => "in",
+        Opcode::Output => "out",
+        Opcode::JumpIfTrue => "jt",
+        Opcode::JumpIfFalse => "jf",
+        Opcode::LessThan => "lt",
+        Opcode::Equals => "eq",
+        Opcode::AdjustRelativeBase => "arb",
+        Opcode::Return => "hlt",
+        Opcode::Unknown => "???",
+    }
+}
+
+// Immediate operands are written as plain numbers, position operands as
+// [address] and relative operands as [rb+offset].
+pub fn format_parameter(parameter:Parameter) -> String {
+    match parameter {
+        Parameter::Position(address) => format!("[{}]", address),
+        Parameter::Immediate(value) => format!("{}", value),
+        Parameter::Relative(offset) if offset < 0 => format!("[rb{}]", offset),
+        Parameter::Relative(offset) => format!("[rb+{}]", offset),
+        Parameter::Unknown => "?".to_string(),
+    }
+}
+
+pub fn format_instruction(instruction:&Instruction) -> String {
+    let parameters:Vec<String> = instruction.parameters[..instruction.length() - 1].iter()
+                                            .map(|&p| format_parameter(p))
+                                            .collect();
+    format!("{:<4}{}", mnemonic(instruction.opcode), parameters.join(", ")).trim_end().to_string()
+}
+
+// Walks the control flow from address 0 and returns every instruction that
+// can be decoded statically. Jump targets are only known for immediate
+// parameters, so immediate values written by Add or Multiply (the usual way
+// a return address gets pushed) are followed as well. Anything missed here
+// is still executed correctly, just by the interpreter.
+pub fn reachable(program:&Vec<i64>) -> BTreeMap<usize, Instruction> {
+    let mut decoder = Intcode::new(program.clone());
+    let mut instructions:BTreeMap<usize, Instruction> = BTreeMap::new();
+    let mut queue:VecDeque<usize> = VecDeque::new();
+    queue.push_back(0);
+
+    while let Some(address) = queue.pop_front() {
+        if address >= program.len() || instructions.contains_key(&address) {
+            continue;
+        }
+        let instruction = decoder.decode(address);
+        if instruction.opcode == Opcode::Unknown || address + instruction.length() > program.len() {
+            continue;
+        }
+        instructions.insert(address, instruction);
+
+        let next = address + instruction.length();
+        match (instruction.opcode, instruction.parameters[0], instruction.parameters[1]) {
+            (Opcode::Return, _, _) => {},
+            (Opcode::JumpIfTrue, Parameter::Immediate(c), target) if c != 0 => push_target(&mut queue, target),
+            (Opcode::JumpIfFalse, Parameter::Immediate(0), target) => push_target(&mut queue, target),
+            (Opcode::JumpIfTrue, Parameter::Immediate(0), _) => queue.push_back(next),
+            (Opcode::JumpIfFalse, Parameter::Immediate(_), _) => queue.push_back(next),
+            (Opcode::JumpIfTrue, _, target) | (Opcode::JumpIfFalse, _, target) => {
+                push_target(&mut queue, target);
+                queue.push_back(next);
+            },
+            (Opcode::Add, a, b) | (Opcode::Multiply, a, b) => {
+                push_target(&mut queue, a);
+                push_target(&mut queue, b);
+                queue.push_back(next);
+            },
+            _ => queue.push_back(next),
+        }
+    }
+
+    instructions
+}
+
+fn push_target(queue:&mut VecDeque<usize>, parameter:Parameter) {
+    if let Parameter::Immediate(target) = parameter {
+        if target >= 0 {
+            queue.push_back(target as usize);
+        }
+    }
+}
+
+// Instruction start addresses: everything the static walk reaches plus every
+// address the coverage saw executed.
+fn instruction_starts(program:&Vec<i64>, coverage:Option<&Coverage>) -> BTreeSet<usize> {
+    let mut starts:BTreeSet<usize> = reachable(program).keys().cloned().collect();
+    if let Some(coverage) = coverage {
+        starts.extend(coverage.hits.keys().filter(|&&a| a < program.len()));
+    }
+    starts
+}
+
+// Returns ranges of image addresses that are not part of any executed
+// instruction.
+pub fn never_executed(program:&Vec<i64>, coverage:&Coverage) -> Vec<(usize, usize)> {
+    let mut decoder = Intcode::new(program.clone());
+    let mut executed = vec![false; program.len()];
+    for &address in coverage.hits.keys() {
+        let length = decoder.decode(address).length();
+        for a in address..(address + length).min(program.len()) {
+            executed[a] = true;
+        }
+    }
+    let mut ranges:Vec<(usize, usize)> = Vec::new();
+    for (address, &hit) in executed.iter().enumerate() {
+        if hit {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.1 + 1 == address => last.1 = address,
+            _ => ranges.push((address, address)),
+        }
+    }
+    ranges
+}
+
+// Disassembles the program image. With coverage every instruction is
+// prefixed with its hit count, or with "-" when it never ran.
+pub fn listing(program:&Vec<i64>, coverage:Option<&Coverage>) -> String {
+    let starts = instruction_starts(program, coverage);
+    let mut decoder = Intcode::new(program.clone());
+    let mut out = String::new();
+    let mut address = 0;
+
+    while address < program.len() {
+        let count = match coverage {
+            Some(coverage) if coverage.count(address) > 0 => format!("{:>10} ", coverage.count(address)),
+            Some(_) => format!("{:>10} ", "-"),
+            None => String::new(),
+        };
+        if starts.contains(&address) {
+            let instruction = decoder.decode(address);
+            let end = (address + instruction.length()).min(program.len());
+            let words:Vec<String> = program[address..end].iter().map(|v| v.to_string()).collect();
+            out.push_str(&format!("{}{:>6}  {:<28} {}\n", count, address, words.join(","), format_instruction(&instruction)));
+            address += instruction.length();
+        } else {
+            let end = starts.range(address..).next().cloned().unwrap_or(program.len());
+            let mut words:Vec<String> = program[address..end.min(address + 8)].iter().map(|v| v.to_string()).collect();
+            if end > address + 8 {
+                words.push("...".to_string());
+            }
+            let location = if end - address > 1 { format!("{}..{}", address, end - 1) } else { address.to_string() };
+            let blank = if coverage.is_some() { format!("{:>10} ", "") } else { String::new() };
+            out.push_str(&format!("{}{:>6}  data {}\n", blank, location, words.join(",")));
+            address = end;
+        }
+    }
+
+    if let Some(coverage) = coverage {
+        let ranges = never_executed(program, coverage);
+        let unexecuted:usize = ranges.iter().map(|(start, end)| end - start + 1).sum();
+        let instructions = starts.len();
+        let hit = starts.iter().filter(|&&a| coverage.count(a) > 0).count();
+        out.push_str(&format!("\n{} runs, {} of {} instructions executed ({:.1}%), {} of {} words never executed\n",
+                              coverage.runs, hit, instructions, 100.0 * hit as f64 / instructions.max(1) as f64, unexecuted, program.len()));
+        out.push_str("Never executed:\n");
+        for (start, end) in ranges {
+            if start == end {
+                out.push_str(&format!("  {}\n", start));
+            } else {
+                out.push_str(&format!("  {}..{}\n", start, end));
+            }
+        }
+    }
+    out
+}
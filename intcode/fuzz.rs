@@ -0,0 +1,296 @@
+use std::fs;
+use std::panic;
+
+use super::generate::{self, Features, Rng};
+use super::vm::{FaultKind, Instruction, Intcode, Observer, Snapshot, Status};
+
+const MAX_TICKS:usize = 10_000;
+const MAX_MEMORY:usize = 10_000;
+
+// A program, its inputs and the tick at which the snapshot check saves the
+// machine state.
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub program: Vec<i64>,
+    pub inputs: Vec<i64>,
+    pub snapshot_at: usize,
+}
+
+impl Case {
+    fn to_line(&self) -> String {
+        let join = |values:&Vec<i64>| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        format!("{};{};{}", join(&self.program), join(&self.inputs), self.snapshot_at)
+    }
+
+    fn from_line(line:&str) -> Case {
+        let parse = |field:&str| field.split(',').filter(|s| !s.is_empty()).map(|s| s.trim().parse().unwrap()).collect();
+        let fields:Vec<&str> = line.split(';').collect();
+        Case { program: parse(fields[0]), inputs: parse(fields[1]), snapshot_at: fields[2].trim().parse().unwrap() }
+    }
+}
+
+// Listens to every event, so that the traced run also checks that observing
+// a program does not change what it does.
+struct EventCount(usize);
+
+impl Observer for EventCount {
+    fn instruction(&mut self, _program_counter:usize, _instruction:&Instruction) { self.0 += 1; }
+    fn memory_write(&mut self, _address:usize, _value:i64) { self.0 += 1; }
+    fn input(&mut self, _value:i64) { self.0 += 1; }
+    fn output(&mut self, _value:i64) { self.0 += 1; }
+    fn status(&mut self, _status:&Status) { self.0 += 1; }
+}
+
+// A word that is an operand: mostly addresses around the program, sometimes
+// negative, huge or arbitrary.
+fn operand(rng:&mut Rng, length:usize) -> i64 {
+    match rng.below(10) {
+        0..=5 => rng.below(length + 5) as i64,
+        6 => rng.range(-10, -1),
+        7 => if rng.chance(50) { i64::max_value() - rng.range(0, 5) } else { i64::min_value() + rng.range(0, 5) },
+        _ => rng.range(-1000, 1000),
+    }
+}
+
+// Instructions with random opcodes and parameter modes, most of them valid.
+fn instruction_stream(rng:&mut Rng) -> Vec<i64> {
+    let length = 1 + rng.below(40);
+    let mut program:Vec<i64> = Vec::new();
+    while program.len() < length {
+        let opcode = match rng.below(20) {
+            0 => 99,
+            1 => 0,
+            2 => rng.range(10, 98),
+            3 => -rng.range(1, 99),
+            _ => rng.range(1, 9),
+        };
+        let mut word = opcode;
+        let mut scale = 100;
+        for _ in 0..3 {
+            let mode = if rng.chance(5) { rng.range(3, 9) } else { rng.range(0, 2) };
+            word += mode * scale;
+            scale *= 10;
+        }
+        program.push(word);
+        for _ in 0..rng.below(4) {
+            program.push(operand(rng, length));
+        }
+    }
+    program
+}
+
+fn random_case(rng:&mut Rng) -> Case {
+    let (program, inputs) = if rng.chance(30) {
+        let features = [Features::Arithmetic, Features::Diagnostic, Features::Complete];
+        let features = features[rng.below(features.len())];
+        generate::program(rng, features)
+    } else {
+        let program = instruction_stream(rng);
+        let inputs = (0..rng.below(5)).map(|_| operand(rng, program.len())).collect();
+        (program, inputs)
+    };
+    Case { program: program, inputs: inputs, snapshot_at: rng.below(50) }
+}
+
+// Runs until the program stops or a limit is reached, checking after every
+// tick that the machine was killed exactly when it reported a fault, and
+// that the fault names the instruction that was about to run.
+fn run(computer:&mut Intcode, ticks:&mut usize, limit:usize) -> Result<(), String> {
+    while computer.status == Status::Running && *ticks < limit && computer.memory.len() <= MAX_MEMORY {
+        let program_counter = computer.program_counter;
+        let word = computer.read(program_counter);
+        computer.tick();
+        *ticks += 1;
+        match (computer.status == Status::Killed, computer.fault()) {
+            (false, None) => {},
+            (true, Some(fault)) => {
+                let value = match fault.kind {
+                    FaultKind::UnknownOpcode(value) | FaultKind::UnknownParameterMode(value) => value,
+                };
+                if fault.program_counter != program_counter || computer.program_counter != program_counter || value != word {
+                    return Err(format!("fault: {:?} raised by instruction {} at {}, program counter now {}",
+                                       fault, word, program_counter, computer.program_counter));
+                }
+            },
+            (killed, fault) => return Err(format!("fault: killed is {} but fault is {:?} after tick at {}", killed, fault, program_counter)),
+        }
+    }
+    Ok(())
+}
+
+fn execute(case:&Case, configure:&dyn Fn(&mut Intcode)) -> Result<Snapshot, String> {
+    let mut computer = Intcode::new(case.program.clone());
+    configure(&mut computer);
+    computer.append_input(&case.inputs);
+    let mut ticks = 0;
+    run(&mut computer, &mut ticks, MAX_TICKS)?;
+    Ok(computer.snapshot())
+}
+
+fn check_invariants(case:&Case) -> Result<(), String> {
+    let plain = execute(case, &|computer| computer.set_decode_cache(false))?;
+    if execute(case, &|_| {})? != plain {
+        return Err("decode cache: the cached run ends in a different state".to_string());
+    }
+    let traced = execute(case, &|computer| {
+        computer.set_decode_cache(false);
+        computer.set_memory_trace(true);
+        computer.set_coverage(true);
+        computer.set_taint(true);
+        computer.add_observer(EventCount(0));
+    })?;
+    if traced != plain {
+        return Err("tracing: the traced run ends in a different state".to_string());
+    }
+
+    let mut computer = Intcode::new(case.program.clone());
+    computer.append_input(&case.inputs);
+    let mut ticks = 0;
+    run(&mut computer, &mut ticks, case.snapshot_at)?;
+    let snapshot = computer.snapshot();
+    let saved_ticks = ticks;
+    run(&mut computer, &mut ticks, MAX_TICKS)?;
+    let end = computer.snapshot();
+    if end != plain {
+        return Err("snapshot: taking a snapshot changes the result".to_string());
+    }
+    for mut restored in vec![computer, Intcode::new(Vec::new())] {
+        restored.restore(&snapshot);
+        if restored.snapshot() != snapshot {
+            return Err("snapshot: the restored state differs from the snapshot".to_string());
+        }
+        let mut ticks = saved_ticks;
+        run(&mut restored, &mut ticks, MAX_TICKS)?;
+        if restored.snapshot() != end {
+            return Err(format!("snapshot: resuming from tick {} ends in a different state", saved_ticks));
+        }
+    }
+    Ok(())
+}
+
+// Checks every invariant, turning a panic into a failure of its own.
+pub fn check(case:&Case) -> Result<(), String> {
+    match panic::catch_unwind(|| check_invariants(case)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                                 .or_else(|| payload.downcast_ref::<String>().cloned())
+                                 .unwrap_or_default();
+            Err(format!("panic: {}", message))
+        },
+    }
+}
+
+fn invariant(message:&str) -> &str {
+    message.split(':').next().unwrap()
+}
+
+// Shrinks a failing case while it keeps failing the same invariant: drops
+// runs of words and inputs, then makes the remaining values smaller.
+pub fn minimize(case:&Case, message:&str) -> Case {
+    let kind = invariant(message).to_string();
+    let fails = |case:&Case| match check(case) {
+        Err(message) => invariant(&message) == kind,
+        Ok(()) => false,
+    };
+    let mut best = case.clone();
+    loop {
+        let mut changed = false;
+        let mut chunk = (best.program.len() / 2).max(1);
+        while chunk > 0 {
+            let mut start = 0;
+            while start < best.program.len() && best.program.len() > 1 {
+                let mut candidate = best.clone();
+                let end = (start + chunk).min(candidate.program.len());
+                candidate.program.drain(start..end);
+                if !candidate.program.is_empty() && fails(&candidate) {
+                    best = candidate;
+                    changed = true;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        let mut i = 0;
+        while i < best.inputs.len() {
+            let mut candidate = best.clone();
+            candidate.inputs.remove(i);
+            if fails(&candidate) {
+                best = candidate;
+                changed = true;
+            } else {
+                i += 1;
+            }
+        }
+        for i in 0..best.program.len() + best.inputs.len() {
+            let value = if i < best.program.len() { best.program[i] } else { best.inputs[i - best.program.len()] };
+            for &smaller in [0, 1, value / 2].iter() {
+                if smaller.abs() >= value.abs() {
+                    continue;
+                }
+                let mut candidate = best.clone();
+                if i < candidate.program.len() {
+                    candidate.program[i] = smaller;
+                } else {
+                    candidate.inputs[i - candidate.program.len()] = smaller;
+                }
+                if fails(&candidate) {
+                    best = candidate;
+                    changed = true;
+                    break;
+                }
+            }
+        }
+        if best.snapshot_at > 0 {
+            let mut candidate = best.clone();
+            candidate.snapshot_at /= 2;
+            if fails(&candidate) {
+                best = candidate;
+                changed = true;
+            }
+        }
+        if !changed {
+            return best;
+        }
+    }
+}
+
+// Replays the cases in the regression file, then checks `count` random cases
+// from `seed`. Failures are minimized and appended to the regression file,
+// each under a comment with the invariant it broke.
+pub fn fuzz(count:usize, seed:u64, regressions:&str) -> bool {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut failures = 0;
+
+    let known = fs::read_to_string(regressions).unwrap_or_default();
+    let replayed:Vec<Case> = known.lines().filter(|l| !l.starts_with('#') && !l.trim().is_empty()).map(Case::from_line).collect();
+    for case in &replayed {
+        if let Err(message) = check(case) {
+            println!("regression {}: {}", case.to_line(), message);
+            failures += 1;
+        }
+    }
+
+    let mut rng = Rng::new(seed);
+    let mut found = String::new();
+    for i in 0..count {
+        let case = random_case(&mut rng);
+        if let Err(message) = check(&case) {
+            let minimized = minimize(&case, &message);
+            let message = check(&minimized).err().unwrap_or(message);
+            println!("case {} (seed {}): {}\n  minimized to {}", i, seed, message, minimized.to_line());
+            found.push_str(&format!("# {}\n{}\n", message, minimized.to_line()));
+            failures += 1;
+        }
+    }
+    panic::set_hook(hook);
+
+    if !found.is_empty() {
+        fs::write(regressions, known + &found).expect("Unable to write regression file");
+        println!("Added the minimized cases to {}", regressions);
+    }
+    println!("{} regressions and {} random cases checked, {} failures", replayed.len(), count, failures);
+    failures == 0
+}
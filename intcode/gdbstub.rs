@@ -0,0 +1,476 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use super::vm::{Intcode, Status};
+
+// The address space is Intcode memory, one address per word. Every word is
+// transferred as 8 little endian bytes, so `m 10,16` reads words 10 and 11.
+const WORD:usize = 8;
+
+// The largest packet the debugger may send or is sent. A memory read takes
+// two hex digits a byte, so it returns half as many bytes at most.
+const PACKET_SIZE:usize = 4000;
+const MAX_MEMORY_READ:usize = PACKET_SIZE / 2;
+
+const TARGET_XML:&str = "<?xml version=\"1.0\"?>
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">
+<target version=\"1.0\">
+  <feature name=\"org.intcode.core\">
+    <reg name=\"program_counter\" bitsize=\"64\" type=\"code_ptr\" regnum=\"0\"/>
+    <reg name=\"relative_base\" bitsize=\"64\" type=\"int64\" regnum=\"1\"/>
+  </feature>
+</target>
+";
+
+// Signals in stop replies: a breakpoint or finished step, an interrupt from
+// the debugger, a fault and an Input instruction waiting for a value.
+const SIGTRAP:u8 = 5;
+const SIGINT:u8 = 2;
+const SIGILL:u8 = 4;
+const SIGTTIN:u8 = 21;
+
+fn checksum(payload:&str) -> u8 {
+    payload.bytes().fold(0u8, |sum, b| sum.wrapping_add(b))
+}
+
+fn to_hex(bytes:&[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text:&str) -> Option<Vec<u8>> {
+    let digits = text.as_bytes();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    digits.chunks(2).map(|pair| {
+        let high = (pair[0] as char).to_digit(16)?;
+        let low = (pair[1] as char).to_digit(16)?;
+        Some((high * 16 + low) as u8)
+    }).collect()
+}
+
+fn word_from_hex(text:&str) -> Option<i64> {
+    let bytes = from_hex(text)?;
+    if bytes.len() != WORD {
+        return None;
+    }
+    let mut word = [0u8; WORD];
+    word.copy_from_slice(&bytes);
+    Some(i64::from_le_bytes(word))
+}
+
+pub struct Stub<W:Write> {
+    computer: Intcode,
+    breakpoints: BTreeSet<usize>,
+    bytes: Receiver<u8>,
+    writer: W,
+    acknowledge: bool,
+    interrupted: bool,
+}
+
+impl<W:Write> Stub<W> {
+    // Serves the debugger on the other end of `reader` and `writer` until it
+    // detaches, kills the program or hangs up. Bytes are read on a thread of
+    // their own so that an interrupt can stop a running program.
+    pub fn serve<R:Read + Send + 'static>(computer:Intcode, reader:R, writer:W) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for byte in reader.bytes() {
+                match byte {
+                    Ok(byte) if sender.send(byte).is_ok() => {},
+                    _ => break,
+                }
+            }
+        });
+        let mut stub = Stub {
+            computer: computer,
+            breakpoints: BTreeSet::new(),
+            bytes: receiver,
+            writer: writer,
+            acknowledge: true,
+            interrupted: false,
+        };
+        while let Some(packet) = stub.receive()? {
+            let (reply, done) = stub.handle(&packet);
+            stub.send(&reply)?;
+            if packet == "QStartNoAckMode" {
+                stub.acknowledge = false;
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.bytes.recv() {
+                Ok(b'$') => {},
+                Ok(0x03) => return Ok(Some("\x03".to_string())),
+                Ok(_) => continue,
+                Err(_) => return Ok(None),
+            }
+            let mut payload:Vec<u8> = Vec::new();
+            loop {
+                match self.bytes.recv() {
+                    Ok(b'#') => break,
+                    Ok(byte) => payload.push(byte),
+                    Err(_) => return Ok(None),
+                }
+            }
+            let mut sum = [0u8; 2];
+            for digit in sum.iter_mut() {
+                match self.bytes.recv() {
+                    Ok(byte) => *digit = byte,
+                    Err(_) => return Ok(None),
+                }
+            }
+            let payload = String::from_utf8_lossy(&payload).to_string();
+            let valid = std::str::from_utf8(&sum).ok().and_then(|s| u8::from_str_radix(s, 16).ok()) == Some(checksum(&payload));
+            if self.acknowledge {
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+                self.writer.flush()?;
+            }
+            if valid {
+                return Ok(Some(payload));
+            }
+        }
+    }
+
+    fn send(&mut self, payload:&str) -> io::Result<()> {
+        loop {
+            write!(self.writer, "${}#{:02x}", payload, checksum(payload))?;
+            self.writer.flush()?;
+            if !self.acknowledge {
+                return Ok(());
+            }
+            loop {
+                match self.bytes.recv() {
+                    Ok(b'+') => return Ok(()),
+                    Ok(b'-') => break,
+                    Ok(0x03) => self.interrupted = true,
+                    Ok(_) => continue,
+                    Err(_) => return Ok(()),
+                }
+            }
+        }
+    }
+
+    // Returns the reply and whether the session is over.
+    fn handle(&mut self, packet:&str) -> (String, bool) {
+        let reply = match packet {
+            "\x03" => format!("S{:02x}", SIGINT),
+            "?" => self.stop_reply(SIGTRAP),
+            "g" => self.registers(),
+            "k" => return (String::new(), true),
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "QStartNoAckMode" => "OK".to_string(),
+            "vCont?" => "vCont;c;s".to_string(),
+            _ if packet.starts_with('D') => return ("OK".to_string(), true),
+            _ if packet.starts_with('H') => "OK".to_string(),
+            _ if packet.starts_with("qSupported") => format!("PacketSize={};qXfer:features:read+;QStartNoAckMode+;swbreak+", PACKET_SIZE),
+            _ if packet.starts_with("qXfer:features:read:target.xml:") => self.target_xml(&packet[32..]),
+            _ if packet.starts_with("qRcmd,") => self.monitor(&packet[6..]),
+            _ if packet.starts_with('G') => self.write_registers(&packet[1..]),
+            _ if packet.starts_with('p') => self.read_register(&packet[1..]),
+            _ if packet.starts_with('P') => self.write_register(&packet[1..]),
+            _ if packet.starts_with('m') => self.read_memory(&packet[1..]),
+            _ if packet.starts_with('M') => self.write_memory(&packet[1..]),
+            _ if packet.starts_with("Z0,") || packet.starts_with("Z1,") => self.breakpoint(&packet[3..], true),
+            _ if packet.starts_with("z0,") || packet.starts_with("z1,") => self.breakpoint(&packet[3..], false),
+            _ if packet.starts_with('c') => self.resume(&packet[1..], false),
+            _ if packet.starts_with('s') => self.resume(&packet[1..], true),
+            _ if packet.starts_with("vCont;c") => self.resume("", false),
+            _ if packet.starts_with("vCont;s") => self.resume("", true),
+            _ => String::new(),
+        };
+        (reply, false)
+    }
+
+    fn stop_reply(&self, signal:u8) -> String {
+        match self.computer.status {
+            Status::Finished => "W00".to_string(),
+            Status::Killed => format!("S{:02x}", SIGILL),
+            Status::Blocked => format!("S{:02x}", SIGTTIN),
+            Status::Running => format!("S{:02x}", signal),
+        }
+    }
+
+    fn registers(&self) -> String {
+        let mut bytes = (self.computer.program_counter as u64).to_le_bytes().to_vec();
+        bytes.extend(&self.computer.relative_base.to_le_bytes());
+        to_hex(&bytes)
+    }
+
+    fn write_registers(&mut self, text:&str) -> String {
+        match (text.get(..16).and_then(word_from_hex), text.get(16..32).and_then(word_from_hex)) {
+            (Some(program_counter), Some(relative_base)) => {
+                self.computer.program_counter = program_counter as usize;
+                self.computer.relative_base = relative_base;
+                "OK".to_string()
+            },
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn read_register(&self, text:&str) -> String {
+        match usize::from_str_radix(text, 16) {
+            Ok(0) => to_hex(&(self.computer.program_counter as u64).to_le_bytes()),
+            Ok(1) => to_hex(&self.computer.relative_base.to_le_bytes()),
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&mut self, text:&str) -> String {
+        let mut parts = text.splitn(2, '=');
+        let register = parts.next().and_then(|r| usize::from_str_radix(r, 16).ok());
+        match (register, parts.next().and_then(word_from_hex)) {
+            (Some(0), Some(value)) => self.computer.program_counter = value as usize,
+            (Some(1), Some(value)) => self.computer.relative_base = value,
+            _ => return "E01".to_string(),
+        }
+        "OK".to_string()
+    }
+
+    fn read_memory(&mut self, text:&str) -> String {
+        let mut parts = text.split(',').map(|v| usize::from_str_radix(v, 16).ok());
+        let (address, length) = match (parts.next(), parts.next()) {
+            (Some(Some(address)), Some(Some(length))) => (address, length),
+            _ => return "E01".to_string(),
+        };
+        let words = match length.checked_add(WORD - 1) {
+            Some(rounded) if length <= MAX_MEMORY_READ => rounded / WORD,
+            _ => return "E01".to_string(),
+        };
+        let mut bytes:Vec<u8> = Vec::new();
+        for word in 0..words {
+            bytes.extend(&self.computer.read(address.wrapping_add(word)).to_le_bytes());
+        }
+        bytes.truncate(length);
+        to_hex(&bytes)
+    }
+
+    fn write_memory(&mut self, text:&str) -> String {
+        let mut parts = text.splitn(2, ':');
+        let location:Vec<Option<usize>> = parts.next().unwrap().split(',').map(|v| usize::from_str_radix(v, 16).ok()).collect();
+        let (address, length, bytes) = match (location.get(0), location.get(1), parts.next().and_then(from_hex)) {
+            (Some(Some(address)), Some(Some(length)), Some(bytes)) => (*address, *length, bytes),
+            _ => return "E01".to_string(),
+        };
+        if bytes.len() != length || length % WORD != 0 {
+            return "E02".to_string();
+        }
+        for (i, chunk) in bytes.chunks(WORD).enumerate() {
+            let mut word = [0u8; WORD];
+            word.copy_from_slice(chunk);
+            self.computer.write(address.wrapping_add(i), i64::from_le_bytes(word));
+        }
+        "OK".to_string()
+    }
+
+    fn breakpoint(&mut self, text:&str, insert:bool) -> String {
+        match text.split(',').next().map(|a| usize::from_str_radix(a, 16)) {
+            Some(Ok(address)) => {
+                if insert {
+                    self.breakpoints.insert(address);
+                } else {
+                    self.breakpoints.remove(&address);
+                }
+                "OK".to_string()
+            },
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn target_xml(&self, range:&str) -> String {
+        let mut parts = range.split(',').map(|v| usize::from_str_radix(v, 16).unwrap_or(0));
+        let offset = parts.next().unwrap_or(0).min(TARGET_XML.len());
+        let end = offset.saturating_add(parts.next().unwrap_or(0)).min(TARGET_XML.len());
+        format!("{}{}", if end == TARGET_XML.len() { "l" } else { "m" }, &TARGET_XML[offset..end])
+    }
+
+    // `monitor input 1,2,3` queues inputs, `monitor status` describes the
+    // machine.
+    fn monitor(&mut self, hex:&str) -> String {
+        let command = from_hex(hex).map(|bytes| String::from_utf8_lossy(&bytes).to_string()).unwrap_or_default();
+        let mut words = command.trim().splitn(2, ' ');
+        let text = match (words.next(), words.next()) {
+            (Some("input"), Some(values)) => {
+                let values:Result<Vec<i64>, _> = values.split(',').map(|v| v.trim().parse()).collect();
+                match values {
+                    Ok(values) => {
+                        self.computer.append_input(&values);
+                        format!("{} inputs queued\n", self.computer.inputs.len())
+                    },
+                    Err(_) => "Inputs are comma separated numbers\n".to_string(),
+                }
+            },
+            (Some("status"), None) => format!("{:?} at {}, relative base {}, {} inputs queued, fault {:?}\n",
+                                              self.computer.status, self.computer.program_counter, self.computer.relative_base,
+                                              self.computer.inputs.len(), self.computer.fault()),
+            _ => "Commands: input <values>, status\n".to_string(),
+        };
+        to_hex(text.as_bytes())
+    }
+
+    // Runs from the program counter (or the given address) until a
+    // breakpoint, the end of a single step, an interrupt, or the program
+    // stopping on its own. Outputs go to the debugger console as they
+    // happen.
+    fn resume(&mut self, address:&str, step:bool) -> String {
+        if let Ok(address) = usize::from_str_radix(address, 16) {
+            self.computer.program_counter = address;
+        }
+        if self.computer.status == Status::Blocked && !self.computer.inputs.is_empty() {
+            self.computer.status = Status::Running;
+        }
+        self.interrupted = false;
+        let mut ticks:u64 = 0;
+        while self.computer.status == Status::Running {
+            self.computer.tick();
+            ticks += 1;
+            while let Some(output) = self.computer.outputs.pop_front() {
+                let text = format!("{}\n", output);
+                if self.send(&format!("O{}", to_hex(text.as_bytes()))).is_err() {
+                    return format!("S{:02x}", SIGINT);
+                }
+            }
+            if step || self.breakpoints.contains(&self.computer.program_counter) {
+                break;
+            }
+            if ticks % 1024 == 0 {
+                match self.bytes.try_recv() {
+                    Ok(0x03) => self.interrupted = true,
+                    Err(TryRecvError::Disconnected) => self.interrupted = true,
+                    _ => {},
+                }
+            }
+            if self.interrupted {
+                return self.stop_reply(SIGINT);
+            }
+        }
+        self.stop_reply(SIGTRAP)
+    }
+}
+
+pub fn serve_tcp(computer:Intcode, port:u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Waiting for a debugger on 127.0.0.1:{}", listener.local_addr()?.port());
+    let (stream, peer) = listener.accept()?;
+    println!("Debugger connected from {}", peer);
+    Stub::serve(computer, stream.try_clone()?, stream)
+}
+
+pub fn serve_stdio(computer:Intcode) -> io::Result<()> {
+    Stub::serve(computer, io::stdin(), io::stdout())
+}
+
+// Stands in for a debugger front end: frames packets, acknowledges replies
+// and collects console output.
+struct Client {
+    stream: TcpStream,
+    console: String,
+}
+
+impl Client {
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0u8; 1];
+        self.stream.read_exact(&mut byte).expect("Stub hung up");
+        byte[0]
+    }
+
+    fn request(&mut self, packet:&str) -> String {
+        write!(self.stream, "${}#{:02x}", packet, checksum(packet)).unwrap();
+        while self.read_byte() != b'+' {}
+        loop {
+            while self.read_byte() != b'$' {}
+            let mut payload:Vec<u8> = Vec::new();
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    byte => payload.push(byte),
+                }
+            }
+            self.read_byte();
+            self.read_byte();
+            self.stream.write_all(b"+").unwrap();
+            let payload = String::from_utf8_lossy(&payload).to_string();
+            if payload.starts_with('O') && payload != "OK" {
+                self.console.push_str(&String::from_utf8_lossy(&from_hex(&payload[1..]).unwrap()));
+            } else {
+                return payload;
+            }
+        }
+    }
+}
+
+// Debugs the compare-to-8 example from day 5 through a local socket and
+// checks every reply.
+pub fn selftest() -> bool {
+    let program = vec![3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+                       1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99];
+    let listener = TcpListener::bind(("127.0.0.1", 0)).expect("Unable to listen");
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        Stub::serve(Intcode::new(program), stream.try_clone().unwrap(), stream).unwrap();
+    });
+    let mut client = Client { stream: TcpStream::connect(("127.0.0.1", port)).expect("Unable to connect"), console: String::new() };
+    let word = |value:i64| to_hex(&value.to_le_bytes());
+    let monitor = |command:&str| format!("qRcmd,{}", to_hex(command.as_bytes()));
+
+    let checks:Vec<(&str, String, Box<dyn Fn(&str) -> bool>)> = vec![
+        ("supported features", "qSupported:swbreak+".to_string(), Box::new(|r:&str| r.contains("qXfer:features:read+"))),
+        ("target description", "qXfer:features:read:target.xml:0,ffff".to_string(), Box::new(|r:&str| r.starts_with('l') && r.contains("relative_base"))),
+        ("initial stop", "?".to_string(), Box::new(|r:&str| r == "S05")),
+        ("registers at start", "g".to_string(), Box::new(move |r:&str| r == word(0) + &word(0))),
+        ("memory read", "m0,10".to_string(), Box::new(move |r:&str| r == word(3) + &word(21))),
+        ("memory read of every address", "m0,ffffffffffffffff".to_string(), Box::new(|r:&str| r == "E01")),
+        ("memory read over the packet size", format!("m0,{:x}", MAX_MEMORY_READ + 1), Box::new(|r:&str| r == "E01")),
+        ("target description past its end", "qXfer:features:read:target.xml:10,ffffffffffffffff".to_string(), Box::new(|r:&str| r.starts_with('l'))),
+        ("monitor command that is not hex", "qRcmd,a\u{e9}0".to_string(), Box::new(|r:&str| from_hex(r).is_some())),
+        ("breakpoint", "Z0,6,1".to_string(), Box::new(|r:&str| r == "OK")),
+        ("blocked on input", "c".to_string(), Box::new(|r:&str| r == "S15")),
+        ("monitor input", monitor("input 8"), Box::new(|r:&str| from_hex(r).map_or(false, |t| t.starts_with(b"1 inputs")))),
+        ("breakpoint hit", "c".to_string(), Box::new(|r:&str| r == "S05")),
+        ("program counter at breakpoint", "p0".to_string(), Box::new(move |r:&str| r == word(6))),
+        ("input stored", "m15,8".to_string(), Box::new(move |r:&str| r == word(8))),
+        ("single step", "s".to_string(), Box::new(|r:&str| r == "S05")),
+        ("jumped on equal", "p0".to_string(), Box::new(move |r:&str| r == word(22))),
+        ("register write", format!("P1={}", word(-7)), Box::new(|r:&str| r == "OK")),
+        ("register read back", "g".to_string(), Box::new(move |r:&str| r == word(22) + &word(-7))),
+        ("memory write", format!("M64,10:{}{}", word(41), word(-42)), Box::new(|r:&str| r == "OK")),
+        ("memory read back", "m64,10".to_string(), Box::new(move |r:&str| r == word(41) + &word(-42))),
+        ("breakpoint removed", "z0,6,1".to_string(), Box::new(|r:&str| r == "OK")),
+        ("run to the end", "c".to_string(), Box::new(|r:&str| r == "W00")),
+    ];
+
+    let mut passed = 0;
+    for (name, packet, check) in &checks {
+        let reply = client.request(packet);
+        if check(&reply) {
+            passed += 1;
+        } else {
+            println!("{}: sent {:?}, got {:?}", name, packet, reply);
+        }
+    }
+    if client.console == "1000\n" {
+        passed += 1;
+    } else {
+        println!("console output: expected \"1000\\n\", got {:?}", client.console);
+    }
+    client.request("k");
+    drop(client);
+    server.join().unwrap();
+
+    println!("{} of {} debugger checks passed", passed, checks.len() + 1);
+    passed == checks.len() + 1
+}
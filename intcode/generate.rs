@@ -0,0 +1,177 @@
+#![allow(dead_code)]
+
+// Random Intcode programs that are valid by construction: every address
+// they touch is inside the image (or, with relative mode, a known distance
+// past it) and every jump goes forward, so each one halts.
+
+// xorshift64*, seeded so that every failing case can be reproduced.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed:u64) -> Rng {
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n:usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // A value in low..=high.
+    pub fn range(&mut self, low:i64, high:i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+
+    pub fn chance(&mut self, percent:usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Features {
+    // Add, multiply and halt in position mode, as on day 2.
+    Arithmetic,
+    // Everything up to day 5: I/O, jumps, comparisons and immediate mode.
+    Diagnostic,
+    // Relative mode, the relative base and memory past the image, as on day 9.
+    Complete,
+}
+
+// Add and multiply instructions at every fourth address followed by a halt
+// and the cells they work on. Only the data cells and address 0 are written,
+// so the program never rewrites an instruction it has yet to run.
+pub fn arithmetic(rng:&mut Rng) -> Vec<i64> {
+    let count = 1 + rng.below(12);
+    let data = 4 * count + 1;
+    let cells = 1 + rng.below(8);
+    let mut program:Vec<i64> = Vec::new();
+    for _ in 0..count {
+        program.push(if rng.chance(50) { 1 } else { 2 });
+        program.push(rng.below(data + cells) as i64);
+        program.push(rng.below(data + cells) as i64);
+        program.push(if rng.chance(10) { 0 } else { (data + rng.below(cells)) as i64 });
+    }
+    program.push(99);
+    for _ in 0..cells {
+        program.push(rng.range(0, 20));
+    }
+    program
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Arithmetic(i64),
+    Input,
+    Output,
+    Jump(i64),
+}
+
+// Straight line code with forward jumps, followed by a halt and a block of
+// data cells, plus the inputs to run it with. Jump targets read through
+// position mode sit in cells of their own that nothing writes. With Complete
+// the relative base points at the data and operands may reach a few cells
+// past the end of the image.
+pub fn program(rng:&mut Rng, features:Features) -> (Vec<i64>, Vec<i64>) {
+    if features == Features::Arithmetic {
+        return (arithmetic(rng), Vec::new());
+    }
+    let complete = features == Features::Complete;
+    let count = 1 + rng.below(24);
+    let kinds:Vec<Kind> = (0..count).map(|_| match rng.below(10) {
+        0 | 1 => Kind::Arithmetic(1),
+        2 => Kind::Arithmetic(2),
+        3 => Kind::Arithmetic(7),
+        4 => Kind::Arithmetic(8),
+        5 => Kind::Input,
+        6 | 7 => Kind::Output,
+        8 => Kind::Jump(5),
+        _ => Kind::Jump(6),
+    }).collect();
+
+    let mut starts:Vec<usize> = Vec::new();
+    let mut address = if complete { 2 } else { 0 };
+    for kind in &kinds {
+        starts.push(address);
+        address += match kind {
+            Kind::Arithmetic(_) => 4,
+            Kind::Jump(_) => 3,
+            _ => 2,
+        };
+    }
+    let halt = address;
+    starts.push(halt);
+    let data = halt + 1;
+    let cells = 1 + rng.below(8);
+    let jumps = kinds.iter().filter(|k| match k { Kind::Jump(_) => true, _ => false }).count();
+    let end = data + cells + jumps;
+    let beyond = if complete { 4 } else { 0 };
+    let magnitude = if complete { 1 << 31 } else { 100 };
+    let relative_base = data as i64;
+
+    let read = |rng:&mut Rng| -> (i64, i64) {
+        match rng.below(if complete { 3 } else { 2 }) {
+            0 => (0, (data + rng.below(cells)) as i64),
+            1 => (1, rng.range(-magnitude, magnitude)),
+            _ => (2, rng.below(end + beyond - data) as i64),
+        }
+    };
+    let write = |rng:&mut Rng| -> (i64, i64) {
+        match (complete, rng.below(3)) {
+            (true, 0) => (2, rng.below(cells) as i64),
+            (true, 1) => (2, (end - data + rng.below(beyond)) as i64),
+            _ => (0, (data + rng.below(cells)) as i64),
+        }
+    };
+
+    let mut program:Vec<i64> = Vec::new();
+    if complete {
+        program.extend(&[109, relative_base]);
+    }
+    let mut target_cells:Vec<i64> = Vec::new();
+    for (i, kind) in kinds.iter().enumerate() {
+        match *kind {
+            Kind::Arithmetic(opcode) => {
+                let (m1, a) = read(rng);
+                let (m2, b) = read(rng);
+                let (m3, c) = write(rng);
+                program.extend(&[opcode + 100 * m1 + 1000 * m2 + 10000 * m3, a, b, c]);
+            },
+            Kind::Input => {
+                let (m1, a) = write(rng);
+                program.extend(&[3 + 100 * m1, a]);
+            },
+            Kind::Output => {
+                let (m1, a) = read(rng);
+                program.extend(&[4 + 100 * m1, a]);
+            },
+            Kind::Jump(opcode) => {
+                let (m1, condition) = read(rng);
+                let target = starts[i + 1 + rng.below(starts.len() - i - 1)] as i64;
+                if rng.chance(30) {
+                    program.extend(&[opcode + 100 * m1, condition, (data + cells + target_cells.len()) as i64]);
+                    target_cells.push(target);
+                } else {
+                    program.extend(&[opcode + 100 * m1 + 1000, condition, target]);
+                }
+            },
+        }
+    }
+    program.push(99);
+    for _ in 0..cells {
+        program.push(rng.range(-20, 20));
+    }
+    program.extend(&target_cells);
+    while program.len() < end {
+        program.push(halt as i64);
+    }
+
+    let inputs = kinds.iter().filter(|k| match k { Kind::Input => true, _ => false }).count();
+    let inputs = if inputs > 0 && rng.chance(10) { inputs - 1 } else { inputs };
+    (program, (0..inputs).map(|_| rng.range(-magnitude, magnitude)).collect())
+}
@@ -0,0 +1,168 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::path::Path;
+
+pub type Rgb = [u8; 3];
+
+// The color of every kind of cell, and of the cells missing from a grid.
+#[derive(Clone, Debug)]
+pub struct Palette<T:Eq + Hash> {
+    colors: HashMap<T, Rgb>,
+    background: Rgb,
+}
+
+impl<T:Eq + Hash> Palette<T> {
+    pub fn new(background:Rgb) -> Palette<T> {
+        Palette { colors: HashMap::new(), background: background }
+    }
+
+    pub fn color(mut self, cell:T, rgb:Rgb) -> Palette<T> {
+        self.colors.insert(cell, rgb);
+        self
+    }
+
+    fn get(&self, cell:Option<&T>) -> Rgb {
+        cell.and_then(|cell| self.colors.get(cell)).cloned().unwrap_or(self.background)
+    }
+}
+
+// An RGB picture, row by row from the top left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    // Draws the grid from its smallest to its largest coordinates, y going
+    // down, with every cell a square of `scale` pixels.
+    pub fn from_grid<T:Eq + Hash>(grid:&HashMap<(i32, i32), T>, palette:&Palette<T>, scale:usize) -> Image {
+        let scale = scale.max(1);
+        if grid.is_empty() {
+            return Image { width: 0, height: 0, pixels: Vec::new() };
+        }
+        let (x_coords, y_coords): (Vec<_>, Vec<_>) = grid.keys().cloned().unzip();
+        let (&x_min, &x_max) = (x_coords.iter().min().unwrap(), x_coords.iter().max().unwrap());
+        let (&y_min, &y_max) = (y_coords.iter().min().unwrap(), y_coords.iter().max().unwrap());
+        let (columns, rows) = ((x_max - x_min + 1) as usize, (y_max - y_min + 1) as usize);
+
+        let mut pixels:Vec<Rgb> = Vec::with_capacity(columns * rows * scale * scale);
+        for y in 0..rows * scale {
+            for x in 0..columns * scale {
+                let cell = (x_min + (x / scale) as i32, y_min + (y / scale) as i32);
+                pixels.push(palette.get(grid.get(&cell)));
+            }
+        }
+        Image { width: columns * scale, height: rows * scale, pixels: pixels }
+    }
+
+    // Binary PPM (P6).
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in &self.pixels {
+            data.extend_from_slice(pixel);
+        }
+        data
+    }
+
+    // An 8 bit RGB PNG. The image data is stored in uncompressed deflate
+    // blocks, which every reader accepts and which needs no compressor.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw:Vec<u8> = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            // Filter type 0: the row as it is.
+            raw.push(0);
+            for pixel in row {
+                raw.extend_from_slice(pixel);
+            }
+        }
+
+        let mut header:Vec<u8> = Vec::new();
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // Bit depth 8, color type 2 (RGB), default compression, filtering
+        // and no interlacing.
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png:Vec<u8> = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    // Writes a PNG, or a PPM for a path ending in .ppm.
+    pub fn save(&self, path:&str) {
+        let data = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("ppm") => self.to_ppm(),
+            _ => self.to_png(),
+        };
+        fs::write(path, data).expect("Unable to write file");
+    }
+}
+
+fn chunk(png:&mut Vec<u8>, kind:&[u8; 4], data:&[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// A zlib stream of stored deflate blocks, at most 65535 bytes each.
+fn zlib_stored(data:&[u8]) -> Vec<u8> {
+    let mut stream:Vec<u8> = vec![0x78, 0x01];
+    let blocks:Vec<&[u8]> = if data.is_empty() { vec![data] } else { data.chunks(0xffff).collect() };
+    for (i, block) in blocks.iter().enumerate() {
+        stream.push(if i + 1 == blocks.len() { 1 } else { 0 });
+        let length = block.len() as u16;
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data:&[u8]) -> u32 {
+    let mut crc:u32 = 0xffff_ffff;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data:&[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// Takes the image=<file> and scale=<pixels> options out of the arguments:
+// the file to save a picture of the puzzle to and the size of its cells.
+pub fn image_options(args:&mut Vec<String>) -> Option<(String, usize)> {
+    let mut path:Option<String> = None;
+    let mut scale = 8;
+    args.retain(|arg| {
+        if arg.starts_with("image=") {
+            path = Some(arg["image=".len()..].to_string());
+        } else if arg.starts_with("scale=") {
+            scale = arg["scale=".len()..].parse().expect("Expected scale=<pixels>");
+        } else {
+            return true;
+        }
+        false
+    });
+    path.map(|path| (path, scale))
+}
@@ -0,0 +1,593 @@
+use std::fs;
+use std::env;
+use std::process;
+use std::process::Command;
+use std::process::Stdio;
+use std::io::Write;
+use std::path::PathBuf;
+
+mod vm;
+mod translate;
+mod compiler;
+mod compiletest;
+mod analysis;
+mod disassembler;
+mod taint;
+mod symbolic;
+mod optimize;
+mod generate;
+mod conformance;
+mod fuzz;
+mod gdbstub;
+mod json;
+mod dap;
+mod session;
+mod loader;
+mod network;
+mod capture;
+mod pipeline;
+
+use vm::Intcode;
+
+const MAX_TICKS:usize = 100_000_000;
+
+fn load_program(path:&str) -> Vec<i64> {
+    loader::load_or_exit(path)
+}
+
+fn parse_inputs(line:&str) -> Vec<i64> {
+    line.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.trim().parse().unwrap())
+        .collect()
+}
+
+// A run's inputs are either comma separated numbers or, prefixed with @, a
+// file whose contents are fed to the program as ASCII.
+fn parse_run(argument:&str) -> Vec<i64> {
+    if argument.starts_with('@') {
+        let text = fs::read_to_string(&argument[1..]).expect("Unable to read file");
+        text.chars().map(|c| c as i64).collect()
+    } else {
+        parse_inputs(argument)
+    }
+}
+
+fn format_run(status:&vm::Status, outputs:&Vec<i64>) -> String {
+    format!("{:?};{}", status, outputs.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","))
+}
+
+fn interpret(program:&Vec<i64>, inputs:&Vec<i64>) -> String {
+    let mut computer = Intcode::new(program.clone());
+    computer.append_input(inputs);
+    computer.execute();
+    format_run(&computer.status, &computer.outputs.iter().cloned().collect())
+}
+
+const DIFFTEST_MAIN:&str = "mod vm;
+mod translated;
+
+use std::io::BufRead;
+
+fn main() {
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let inputs:Vec<i64> = line.split(',').filter(|s| !s.is_empty()).map(|s| s.parse().unwrap()).collect();
+        let mut computer = translated::Translated::new();
+        computer.append_input(&inputs);
+        computer.execute();
+        let outputs:Vec<String> = computer.outputs.iter().map(|v| v.to_string()).collect();
+        println!(\"{:?};{}\", computer.status, outputs.join(\",\"));
+    }
+}
+";
+
+// Translates the program, compiles the result next to a copy of the VM and
+// checks that it produces the same outputs and final status as the
+// interpreter for every input set.
+fn difftest(path:&str, cases:&Vec<Vec<i64>>) -> bool {
+    let program = load_program(path);
+    let dir:PathBuf = env::temp_dir().join(format!("intcode-difftest-{}", process::id()));
+    fs::create_dir_all(&dir).expect("Unable to create build directory");
+    fs::write(dir.join("vm.rs"), include_str!("vm.rs")).unwrap();
+    fs::write(dir.join("translated.rs"), translate::translate(&program, path)).unwrap();
+    fs::write(dir.join("main.rs"), DIFFTEST_MAIN).unwrap();
+
+    let binary = dir.join("translated");
+    let status = Command::new("rustc")
+                         .args(&["-O", "--edition", "2018", "-o"])
+                         .arg(&binary)
+                         .arg(dir.join("main.rs"))
+                         .status()
+                         .expect("Unable to run rustc");
+    if !status.success() {
+        println!("{}: translated program does not compile", path);
+        return false;
+    }
+
+    let mut child = Command::new(&binary)
+                            .stdin(Stdio::piped())
+                            .stdout(Stdio::piped())
+                            .spawn()
+                            .expect("Unable to run translated program");
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        for inputs in cases {
+            let line:Vec<String> = inputs.iter().map(|v| v.to_string()).collect();
+            writeln!(stdin, "{}", line.join(",")).unwrap();
+        }
+    }
+    let output = child.wait_with_output().unwrap();
+    let translated:Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(|l| l.to_string()).collect();
+    fs::remove_dir_all(&dir).ok();
+
+    let mut passed = translated.len() == cases.len();
+    for (i, inputs) in cases.iter().enumerate() {
+        let expected = interpret(&program, inputs);
+        match translated.get(i) {
+            Some(actual) if *actual == expected => {},
+            actual => {
+                println!("{}: inputs {:?} interpreted as {:?}, translated as {:?}", path, inputs, expected, actual);
+                passed = false;
+            },
+        }
+    }
+    println!("{}: {} cases {}", path, cases.len(), if passed { "match" } else { "DIFFER" });
+    passed
+}
+
+fn default_difftests() -> Vec<(String, Vec<Vec<i64>>)> {
+    let mut beam:Vec<Vec<i64>> = Vec::new();
+    for y in (0..50).step_by(7) {
+        for x in (0..50).step_by(7) {
+            beam.push(vec![x, y]);
+        }
+    }
+    beam.push(vec![600, 900]);
+    vec![
+        ("../day5/input.txt".to_string(), vec![vec![1], vec![5]]),
+        ("../day9/input.txt".to_string(), vec![vec![1], vec![2]]),
+        ("../day19/input.txt".to_string(), beam),
+    ]
+}
+
+// Optimizes the program using the cases as analysis runs and checks that the
+// result produces the same outputs and final status for each of them.
+// Returns whether they all match and whether the program got smaller; one
+// that comes out the same size still passes, as the optimizer does not
+// promise to shrink every program.
+fn opttest(path:&str, program:&Vec<i64>, cases:&Vec<Vec<i64>>) -> (bool, bool) {
+    let optimized = optimize::optimize(program, cases, MAX_TICKS);
+    let mut passed = true;
+    for inputs in cases {
+        let expected = interpret(program, inputs);
+        let actual = interpret(&optimized.program, inputs);
+        if actual != expected {
+            println!("{}: inputs {:?} interpreted as {:?}, optimized as {:?}", path, inputs, expected, actual);
+            passed = false;
+        }
+    }
+    let smaller = optimized.program.len() < program.len();
+    let size = if smaller {
+        format!("{} words smaller", program.len() - optimized.program.len())
+    } else {
+        "same size".to_string()
+    };
+    println!("{}: {} -> {} words ({}), {} folded, {} jumps threaded, {} cases {}", path, program.len(), optimized.program.len(),
+             size, optimized.folded, optimized.threaded, cases.len(), if passed { "match" } else { "DIFFER" });
+    (passed, smaller)
+}
+
+// The springdroid scripts from day 21 run through most of its image, dead
+// code included. The last two programs execute instructions whose operands
+// run past the end of the image.
+fn default_opttests() -> Vec<(String, Vec<i64>, Vec<Vec<i64>>)> {
+    let walk = "NOT A J\nNOT B T\nOR T J\nNOT C T\nOR T J\nAND D J\nWALK\n";
+    let run = "NOT A J\nNOT B T\nOR T J\nNOT C T\nOR T J\nAND D J\nNOT E T\nNOT T T\nOR H T\nAND T J\nRUN\n";
+    let mut tests:Vec<(String, Vec<i64>, Vec<Vec<i64>>)> = default_difftests().into_iter()
+        .map(|(path, cases)| { let program = load_program(&path); (path, program, cases) })
+        .collect();
+    tests.push(("../day21/input.txt".to_string(), load_program("../day21/input.txt"),
+                vec![walk, run, "NOT A J\nWALK\n"].iter().map(|s| s.chars().map(|c| c as i64).collect()).collect()));
+    for program in vec![vec![4], vec![1, 0, 0, 0, 4]] {
+        let name = program.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        tests.push((name, program, vec![Vec::new()]));
+    }
+    tests
+}
+
+// Runs a network of the program for a number of rounds (1000), or until
+// nothing can happen any more.
+fn simulate(path:&str, rounds:Option<&String>, options:&[String], capture:bool) -> network::Network {
+    let program = load_program(path);
+    let rounds:usize = rounds.map_or(1000, |r| r.parse().unwrap());
+    let config = network::Config::parse(options).unwrap_or_else(|error| {
+        println!("{}", error);
+        process::exit(2);
+    });
+    let mut network = network::Network::new(&program, config);
+    network.set_capture(capture);
+    for _ in 0..rounds {
+        if network.finished() || (network.idle() && network.nat.is_empty()) {
+            break;
+        }
+        network.round();
+    }
+    network
+}
+
+fn usage() {
+    println!("Usage: intcode <command> [arguments]");
+    println!();
+    println!("Commands:");
+    println!("  run <program> [inputs]           run a program with comma separated inputs");
+    println!("  record <program> <inputs> <file> run a program and save its I/O session");
+    println!("  replay <program> <file>          check that a program still produces a recorded session");
+    println!("  translate <program> [output.rs]  emit a Rust module for the program");
+    println!("  pack <program> <image> [size]    write a binary image with 1, 2, 4 or 8 byte words");
+    println!("  unpack <image> [program]         write a binary image back out as text");
+    println!("  difftest [<program> <inputs>...] compare translated and interpreted runs");
+    println!("  compile <source> [output] [map]  compile a program to Intcode, writing a symbol map for debuggers");
+    println!("  compiletest                      run the compiler test suite");
+    println!("  conformance [count] [seed]       compare every interpreter on examples and random programs");
+    println!("  fuzz [count] [seed] [file]       check VM invariants on random programs, minimizing failures into file");
+    println!("  analyze <program> [inputs]       classify memory and detect self-modifying code");
+    println!("  disassemble <program>            print a disassembly listing");
+    println!("  coverage <program> <run>...      disassembly annotated with hit counts merged over runs");
+    println!("  taint <program> [inputs] [cells] trace which inputs and cells (1=12,2) reach outputs and branches");
+    println!("  optimize <program> <output> [<run>...] fold constants and drop dead code, checked on the runs");
+    println!("  opttest [<program> <inputs>...] compare optimized and original runs");
+    println!("  gdbserver <program> [inputs] [port|-] serve a GDB remote debugger on a local port (1234) or stdio");
+    println!("  gdbtest                          drive the debugger stub from a local client");
+    println!("  dap                              serve the Debug Adapter Protocol on stdio");
+    println!("  daptest                          drive the debug adapter from a local client");
+    println!("  network <program> [rounds] [options] simulate a day 23 network and print per node statistics");
+    println!("  capture <program> <file> [rounds] [options] simulate a network and save every packet;");
+    println!("                                   options nodes=N nat=A[:T] broadcast=A sink=A idle=N ticks=N clear,");
+    println!("                                   seed=N and schedule=round-robin, blocked, random or quantum:N");
+    println!("  schedulers <program> [seeds] [options] check that every scheduler reproduces the same answers");
+    println!("  packets <file> [address]         print the timeline of a capture, or of one address");
+    println!("  inject <program> <file> <address> replay the captured packets of one node into a lone NIC");
+    println!("  amplifiers <program> <phases> [loop] run a chain of amplifiers, or a feedback loop, fed a single 0");
+    println!("  solve <program> <symbols> <goal> find symbol values that reach a goal, e.g.");
+    println!("                                   solve day2.txt m1=0..99,m2=0..99 m0=19690720");
+    println!("                                   solve day19.txt in=0..49,in=0..49 out0=1");
+    println!();
+    println!("Inputs are comma separated numbers, or @file to send the file as ASCII.");
+    println!("Programs are text, with # comments, or binary images written by pack.");
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let command = if args.len() > 1 { args[1].as_str() } else { "" };
+
+    match command {
+        "run" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            let inputs = if args.len() > 3 { parse_run(&args[3]) } else { Vec::new() };
+            println!("{}", interpret(&program, &inputs));
+        },
+        "record" if args.len() > 4 => {
+            let program = load_program(&args[2]);
+            let mut computer = Intcode::new(program.clone());
+            computer.set_recording(true);
+            computer.append_input(&parse_run(&args[3]));
+            computer.execute();
+            let session = session::Session::from_run(&program, &[], &computer);
+            session.save(&args[4]);
+            println!("Recorded {} inputs and {} outputs ({:?}) to {}", session.inputs.len(), session.outputs.len(), computer.status, args[4]);
+        },
+        "replay" if args.len() > 3 => {
+            let program = load_program(&args[2]);
+            match session::Session::load(&args[3]).and_then(|session| session.replay(&program)) {
+                Ok(()) => println!("Replay of {} matches", args[3]),
+                Err(error) => {
+                    println!("Replay of {} differs: {}", args[3], error);
+                    process::exit(1);
+                },
+            }
+        },
+        "translate" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            let module = translate::translate(&program, &args[2]);
+            if args.len() > 3 {
+                fs::write(&args[3], module).expect("Unable to write file");
+            } else {
+                print!("{}", module);
+            }
+        },
+        "pack" if args.len() > 3 => {
+            let program = load_program(&args[2]);
+            let size = if args.len() > 4 { args[4].parse().unwrap() } else { loader::word_size(&program) };
+            match loader::to_image(&program, size) {
+                Ok(image) => {
+                    fs::write(&args[3], &image).expect("Unable to write file");
+                    println!("Packed {} words into {} bytes ({} byte words)", program.len(), image.len(), size);
+                },
+                Err(error) => {
+                    println!("{}: {}", args[2], error);
+                    process::exit(1);
+                },
+            }
+        },
+        "unpack" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            let text = program.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+            if args.len() > 3 {
+                fs::write(&args[3], text).expect("Unable to write file");
+            } else {
+                println!("{}", text);
+            }
+        },
+        "difftest" => {
+            let tests = if args.len() > 2 {
+                vec![(args[2].clone(), args[3..].iter().map(|s| parse_inputs(s)).collect())]
+            } else {
+                default_difftests()
+            };
+            let mut passed = true;
+            for (path, cases) in tests {
+                passed &= difftest(&path, &cases);
+            }
+            if !passed {
+                process::exit(1);
+            }
+        },
+        "compile" if args.len() > 2 => {
+            let source = fs::read_to_string(&args[2]).expect("Unable to read file");
+            match compiler::compile_with_symbols(&source) {
+                Ok((program, symbols)) => {
+                    if args.len() > 4 {
+                        fs::write(&args[4], symbols.to_text()).expect("Unable to write file");
+                    }
+                    let image = program.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+                    if args.len() > 3 {
+                        fs::write(&args[3], image).expect("Unable to write file");
+                    } else {
+                        println!("{}", image);
+                    }
+                },
+                Err(error) => {
+                    println!("{}: {}", args[2], error);
+                    process::exit(1);
+                },
+            }
+        },
+        "analyze" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            let inputs = if args.len() > 3 { parse_run(&args[3]) } else { Vec::new() };
+            analysis::report(&analysis::analyze(&program, &inputs, MAX_TICKS));
+        },
+        "disassemble" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            print!("{}", disassembler::listing(&program, None));
+        },
+        "coverage" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            let runs:Vec<Vec<i64>> = if args.len() > 3 { args[3..].iter().map(|s| parse_run(s)).collect() } else { vec![Vec::new()] };
+            let mut coverage = vm::Coverage::default();
+            for inputs in runs {
+                let mut computer = Intcode::new(program.clone());
+                computer.set_coverage(true);
+                computer.append_input(&inputs);
+                computer.execute();
+                coverage.merge(computer.coverage().unwrap());
+            }
+            print!("{}", disassembler::listing(&program, Some(&coverage)));
+        },
+        "taint" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            let inputs = if args.len() > 3 { parse_run(&args[3]) } else { Vec::new() };
+            let cells:Vec<(usize, Option<i64>)> = if args.len() > 4 {
+                args[4].split(',').map(|cell| {
+                    let mut parts = cell.split('=');
+                    let address = parts.next().unwrap().trim().parse().unwrap();
+                    (address, parts.next().map(|v| v.trim().parse().unwrap()))
+                }).collect()
+            } else {
+                Vec::new()
+            };
+            taint::report(&taint::run(&program, &inputs, &cells, MAX_TICKS));
+        },
+        "optimize" if args.len() > 3 => {
+            let program = load_program(&args[2]);
+            let runs:Vec<Vec<i64>> = if args.len() > 4 { args[4..].iter().map(|s| parse_run(s)).collect() } else { vec![Vec::new()] };
+            let optimized = optimize::optimize(&program, &runs, MAX_TICKS);
+            optimize::report(&program, &optimized);
+            let image = optimized.program.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+            fs::write(&args[3], image).expect("Unable to write file");
+            for inputs in &runs {
+                if interpret(&program, inputs) != interpret(&optimized.program, inputs) {
+                    println!("Optimized program differs on inputs {:?}", inputs);
+                    process::exit(1);
+                }
+            }
+        },
+        "opttest" => {
+            let tests = if args.len() > 2 {
+                vec![(args[2].clone(), load_program(&args[2]), args[3..].iter().map(|s| parse_run(s)).collect())]
+            } else {
+                default_opttests()
+            };
+            let mut passed = true;
+            let mut smaller = 0;
+            for (path, program, cases) in &tests {
+                let (matched, shrunk) = opttest(path, program, cases);
+                passed &= matched;
+                smaller += shrunk as usize;
+            }
+            println!("{} of {} programs optimized to fewer words", smaller, tests.len());
+            if !passed {
+                process::exit(1);
+            }
+        },
+        "network" if args.len() > 2 => {
+            let network = simulate(&args[2], args.get(3), &args[args.len().min(4)..], false);
+            network.report();
+        },
+        "schedulers" if args.len() > 2 => {
+            let program = load_program(&args[2]);
+            let seeds = if args.len() > 3 { args[3].parse().unwrap() } else { 5 };
+            let config = network::Config::parse(&args[args.len().min(4)..]).unwrap_or_else(|error| {
+                println!("{}", error);
+                process::exit(2);
+            });
+            if !network::compare_schedulers(&program, &config, seeds, 1_000_000) {
+                process::exit(1);
+            }
+        },
+        "capture" if args.len() > 3 => {
+            let network = simulate(&args[2], args.get(4), &args[args.len().min(5)..], true);
+            let capture = capture::Capture::from_network(&network);
+            capture.save(&args[3]);
+            println!("Captured {} packets over {} turns to {}", capture.packets.len(), network.tick, args[3]);
+        },
+        "packets" if args.len() > 2 => {
+            let capture = capture::Capture::load(&args[2]).unwrap_or_else(|error| {
+                println!("{}", error);
+                process::exit(1);
+            });
+            capture.timeline(args.get(3).map(|a| a.parse().unwrap()));
+        },
+        "inject" if args.len() > 4 => {
+            let program = load_program(&args[2]);
+            let capture = capture::Capture::load(&args[3]).unwrap_or_else(|error| {
+                println!("{}", error);
+                process::exit(1);
+            });
+            let address:i64 = args[4].parse().unwrap();
+            match capture.replay(&program, address, network::Config::day23().max_ticks) {
+                Ok(sent) => {
+                    for p in &sent {
+                        println!("{:>8} {:>7} -> {:<11} {:>16} {:>16}", p.tick, p.source, p.destination, p.x, p.y);
+                    }
+                    println!("Node {} sent the {} captured packets again ({} in the replay)", address,
+                             capture.packets.iter().filter(|p| p.source == address).count(), sent.len());
+                },
+                Err(error) => {
+                    println!("Node {} differs from the capture: {}", address, error);
+                    process::exit(1);
+                },
+            }
+        },
+        "amplifiers" if args.len() > 3 => {
+            let program = load_program(&args[2]);
+            let feedback = args.get(4).map_or(false, |a| a == "loop");
+            let mut amps = pipeline::Builder::amplifiers(&program, &parse_inputs(&args[3]), feedback).unwrap_or_else(|stop| {
+                println!("{}", stop);
+                process::exit(1);
+            });
+            match amps.run(10_000, MAX_TICKS).map(|outputs| outputs.clone()) {
+                Ok(outputs) => println!("{:?} after {} instructions", outputs, amps.ticks),
+                Err(stop) => {
+                    println!("{} ({:?} sent so far)", stop, amps.outputs);
+                    process::exit(1);
+                },
+            }
+        },
+        "solve" if args.len() > 4 => {
+            let program = load_program(&args[2]);
+            let mut inputs:Vec<Option<i64>> = Vec::new();
+            let mut symbols:Vec<symbolic::Symbol> = Vec::new();
+            for item in args[3].split(',') {
+                let mut parts = item.split('=');
+                let name = parts.next().unwrap().trim();
+                let domain = match parts.next() {
+                    Some(domain) => domain,
+                    None => {
+                        inputs.push(Some(name.parse().unwrap()));
+                        continue;
+                    },
+                };
+                let bounds:Vec<i64> = domain.split("..").map(|v| v.trim().parse().unwrap()).collect();
+                let source = if name == "in" {
+                    inputs.push(None);
+                    symbolic::Source::Input
+                } else {
+                    symbolic::Source::Memory(name.trim_start_matches('m').parse().unwrap())
+                };
+                let name = if name == "in" { format!("in{}", inputs.len() - 1) } else { name.to_string() };
+                symbols.push(symbolic::Symbol { name: name, source: source, low: bounds[0], high: bounds[1] });
+            }
+            let mut parts = args[4].split('=');
+            let place = parts.next().unwrap();
+            let value:i64 = parts.next().unwrap().parse().unwrap();
+            let goal = if place.starts_with("out") {
+                symbolic::Goal::Output(place[3..].parse().unwrap(), value)
+            } else {
+                symbolic::Goal::Memory(place.trim_start_matches('m').parse().unwrap(), value)
+            };
+            let search = symbolic::search(&program, &inputs, &symbols, &goal, MAX_TICKS);
+            println!("Explored {} paths", search.paths);
+            for candidate in &search.candidates {
+                println!("Path {} ({:?}, {} constraints): goal expression {}", candidate.path, candidate.end, candidate.constraints, candidate.expression);
+            }
+            match search.solution {
+                Some(values) => {
+                    for (symbol, value) in symbols.iter().zip(values.iter()) {
+                        println!("{} = {}", symbol.name, value);
+                    }
+                },
+                None => {
+                    println!("No solution found");
+                    process::exit(1);
+                },
+            }
+        },
+        "conformance" => {
+            let count = if args.len() > 2 { args[2].parse().unwrap() } else { 1000 };
+            let seed = if args.len() > 3 { args[3].parse().unwrap() } else { 1 };
+            if !conformance::run(count, seed) {
+                process::exit(1);
+            }
+        },
+        "fuzz" => {
+            let count = if args.len() > 2 { args[2].parse().unwrap() } else { 10000 };
+            let seed = if args.len() > 3 { args[3].parse().unwrap() } else { 1 };
+            let regressions = if args.len() > 4 { args[4].as_str() } else { "fuzz-regressions.txt" };
+            if !fuzz::fuzz(count, seed, regressions) {
+                process::exit(1);
+            }
+        },
+        "gdbserver" if args.len() > 2 => {
+            let mut computer = Intcode::new(load_program(&args[2]));
+            if args.len() > 3 {
+                computer.append_input(&parse_run(&args[3]));
+            }
+            let result = match args.get(4).map(|s| s.as_str()) {
+                Some("-") => gdbstub::serve_stdio(computer),
+                port => gdbstub::serve_tcp(computer, port.map_or(1234, |p| p.parse().unwrap())),
+            };
+            if let Err(error) = result {
+                eprintln!("gdbserver: {}", error);
+                process::exit(1);
+            }
+        },
+        "dap" => {
+            if let Err(error) = dap::serve_stdio() {
+                eprintln!("dap: {}", error);
+                process::exit(1);
+            }
+        },
+        "daptest" => {
+            if !dap::selftest() {
+                process::exit(1);
+            }
+        },
+        "gdbtest" => {
+            if !gdbstub::selftest() {
+                process::exit(1);
+            }
+        },
+        "compiletest" => {
+            if !compiletest::run() {
+                process::exit(1);
+            }
+        },
+        _ => {
+            usage();
+            process::exit(2);
+        },
+    }
+}
@@ -0,0 +1,138 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use std::fs;
+use std::process;
+
+// Intcode programs as text: numbers separated by a comma, whitespace or
+// both, where `#` starts a comment that runs to the end of the line.
+//
+//     # day 9 quine
+//     109,1,204,-1,
+//     1001,100,1,100    # count
+//     1008,100,16,101, 1006,101,0, 99
+//
+// Large generated programs can also be stored as binary images: the magic
+// bytes `INTC`, one byte with the word size (1, 2, 4 or 8), then every word
+// as a little endian two's complement number of that size.
+pub const MAGIC:&[u8; 4] = b"INTC";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {} {:?}", self.line, self.column, self.message, self.token)
+    }
+}
+
+pub fn parse(text:&str) -> Result<Vec<i64>, ParseError> {
+    let mut program:Vec<i64> = Vec::new();
+    // Where the last comma was, while no number has followed it yet.
+    let mut comma:Option<(usize, usize)> = None;
+    for (n, line) in text.lines().enumerate() {
+        let code = match line.find('#') {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        let chars:Vec<char> = code.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c.is_whitespace() {
+                i += 1;
+            } else if c == ',' {
+                if program.is_empty() || comma.is_some() {
+                    return Err(ParseError { line: n + 1, column: i + 1, token: ",".to_string(), message: "expected a number before".to_string() });
+                }
+                comma = Some((n + 1, i + 1));
+                i += 1;
+            } else {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && chars[i] != ',' {
+                    i += 1;
+                }
+                let token:String = chars[start..i].iter().collect();
+                let error = |message:&str| ParseError { line: n + 1, column: start + 1, token: token.clone(), message: message.to_string() };
+                let digits = token.trim_start_matches(|c| c == '-' || c == '+');
+                if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) || token.len() - digits.len() > 1 {
+                    return Err(error("expected a number, found"));
+                }
+                program.push(token.parse().map_err(|_| error("number out of range"))?);
+                comma = None;
+            }
+        }
+    }
+    Ok(program)
+}
+
+// The smallest word size that holds every value of the program.
+pub fn word_size(program:&Vec<i64>) -> u8 {
+    let fits = |bits:u32| program.iter().all(|&v| v >= -(1i64 << (bits - 1)) && v < (1i64 << (bits - 1)));
+    [1u8, 2, 4].iter().cloned().find(|&size| fits(size as u32 * 8)).unwrap_or(8)
+}
+
+pub fn to_image(program:&Vec<i64>, word_size:u8) -> Result<Vec<u8>, String> {
+    if ![1, 2, 4, 8].contains(&word_size) {
+        return Err(format!("word size {} is not 1, 2, 4 or 8", word_size));
+    }
+    let bits = word_size as u32 * 8;
+    let mut image = MAGIC.to_vec();
+    image.push(word_size);
+    for (address, &value) in program.iter().enumerate() {
+        if bits < 64 && (value < -(1i64 << (bits - 1)) || value >= (1i64 << (bits - 1))) {
+            return Err(format!("{} at address {} does not fit in {} bytes", value, address, word_size));
+        }
+        image.extend(&value.to_le_bytes()[..word_size as usize]);
+    }
+    Ok(image)
+}
+
+pub fn from_image(image:&[u8]) -> Result<Vec<i64>, String> {
+    if image.len() < 5 || &image[..4] != MAGIC {
+        return Err("not an Intcode image".to_string());
+    }
+    let word_size = image[4] as usize;
+    if ![1, 2, 4, 8].contains(&word_size) {
+        return Err(format!("word size {} is not 1, 2, 4 or 8", word_size));
+    }
+    let words = &image[5..];
+    if words.len() % word_size != 0 {
+        return Err(format!("{} bytes of words is not a multiple of the word size {}", words.len(), word_size));
+    }
+    Ok(words.chunks(word_size).map(|word| {
+        let mut bytes = [0u8; 8];
+        bytes[..word_size].copy_from_slice(word);
+        // Sign extend from the top bit of the last byte.
+        if word[word_size - 1] & 0x80 != 0 {
+            for byte in bytes[word_size..].iter_mut() {
+                *byte = 0xff;
+            }
+        }
+        i64::from_le_bytes(bytes)
+    }).collect())
+}
+
+// Loads a text program or a binary image, telling them apart by the magic
+// bytes.
+pub fn load(path:&str) -> Result<Vec<i64>, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if data.starts_with(MAGIC) {
+        return from_image(&data).map_err(|e| format!("{}: {}", path, e));
+    }
+    let text = String::from_utf8(data).map_err(|_| format!("{}: neither text nor an Intcode image", path))?;
+    parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+// For binaries that cannot do anything without their program.
+pub fn load_or_exit(path:&str) -> Vec<i64> {
+    load(path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    })
+}
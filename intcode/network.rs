@@ -0,0 +1,390 @@
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, VecDeque};
+
+use super::generate::Rng;
+use super::vm::{Intcode, Status};
+
+// A network of Intcode computers, as in day 23: every node boots with its
+// address as input, sends packets as three outputs (destination, x, y) and
+// reads -1 whenever no packet is waiting for it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Packet {
+    pub source: i64,
+    pub destination: i64,
+    pub x: i64,
+    pub y: i64,
+    pub tick: u64,
+}
+
+// What happens to packets sent to an address that is not a node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Special {
+    // Keeps the last packet and sends it to `target` whenever the network
+    // is idle.
+    Nat { target: i64 },
+    // Delivers the packet to every node but its source.
+    Broadcast,
+    // Swallows the packet.
+    Sink,
+}
+
+// How nodes take turns. Every round gives each node one turn, so rounds and
+// idle detection mean the same under every policy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheduler {
+    // Address order, one value of input per turn, as in day 23.
+    RoundRobin,
+    // Address order, with the whole queue fed at once.
+    UntilBlocked,
+    // A new order every round, shuffled from the seed.
+    Random,
+    // Address order, one value of input per turn and at most this many
+    // instructions, so that nodes are interrupted mid-packet.
+    Quantum(usize),
+}
+
+impl Scheduler {
+    pub fn parse(text:&str) -> Result<Scheduler, String> {
+        let mut parts = text.splitn(2, ':');
+        match (parts.next().unwrap(), parts.next()) {
+            ("round-robin", None) => Ok(Scheduler::RoundRobin),
+            ("blocked", None) => Ok(Scheduler::UntilBlocked),
+            ("random", None) => Ok(Scheduler::Random),
+            ("quantum", Some(n)) => n.parse().ok().filter(|&n| n > 0).map(Scheduler::Quantum)
+                                     .ok_or(format!("quantum: expected a positive number, found {:?}", n)),
+            _ => Err(format!("unknown scheduler {:?}, expected round-robin, blocked, random or quantum:N", text)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub nodes: usize,
+    pub special: BTreeMap<i64, Special>,
+    // How many -1 reads in a row, without sending anything, make a node
+    // idle.
+    pub idle_reads: usize,
+    // Instructions a node may run in one turn before it has to yield.
+    pub max_ticks: usize,
+    pub scheduler: Scheduler,
+    pub seed: u64,
+}
+
+impl Config {
+    // The network of the puzzle: fifty nodes and a NAT at 255 waking up
+    // address 0.
+    pub fn day23() -> Config {
+        let mut special = BTreeMap::new();
+        special.insert(255, Special::Nat { target: 0 });
+        Config { nodes: 50, special: special, idle_reads: 1, max_ticks: 100_000, scheduler: Scheduler::RoundRobin, seed: 1 }
+    }
+
+    // Options of the form nodes=N, nat=A[:T], broadcast=A, sink=A, idle=N,
+    // ticks=N, schedule=S and seed=N, applied on top of the puzzle's
+    // network. clear drops the special addresses set so far.
+    pub fn parse(options:&[String]) -> Result<Config, String> {
+        let mut config = Config::day23();
+        for option in options {
+            let mut parts = option.splitn(2, '=');
+            let (key, value) = (parts.next().unwrap(), parts.next().unwrap_or(""));
+            let number = |text:&str| text.trim().parse::<i64>().map_err(|_| format!("{}: expected a number, found {:?}", key, text));
+            // Counts, sizes and node addresses, which must not be negative.
+            let count = |text:&str| text.trim().parse::<u64>().map_err(|_| format!("{}: expected a non-negative number, found {:?}", key, text));
+            match key {
+                "nodes" => config.nodes = count(value)? as usize,
+                "idle" => config.idle_reads = count(value)? as usize,
+                "ticks" => config.max_ticks = count(value)?.max(1) as usize,
+                "schedule" => config.scheduler = Scheduler::parse(value)?,
+                "seed" => config.seed = count(value)?,
+                "nat" => {
+                    let mut parts = value.splitn(2, ':');
+                    let address = number(parts.next().unwrap())?;
+                    let target = parts.next().map(|t| count(t)).unwrap_or(Ok(0))? as i64;
+                    config.special.insert(address, Special::Nat { target: target });
+                },
+                "broadcast" => { config.special.insert(number(value)?, Special::Broadcast); },
+                "sink" => { config.special.insert(number(value)?, Special::Sink); },
+                "clear" => config.special.clear(),
+                _ => return Err(format!("unknown option {:?}", option)),
+            }
+        }
+        if config.nodes == 0 {
+            return Err("a network needs at least one node".to_string());
+        }
+        if let Some(address) = config.special.keys().find(|&&a| a >= 0 && (a as usize) < config.nodes) {
+            return Err(format!("address {} is both a node and a special address", address));
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Stats {
+    pub sent: u64,
+    pub received: u64,
+    pub dropped: u64,
+    // Values waiting in the input queue, now and at most.
+    pub queue_depth: usize,
+    pub max_queue_depth: usize,
+    // Turns that read -1 and sent nothing, in total and in a row up to
+    // now.
+    pub idle_turns: u64,
+    pub idle_streak: usize,
+}
+
+pub struct Network {
+    pub config: Config,
+    pub nodes: Vec<Intcode>,
+    pub queues: Vec<VecDeque<i64>>,
+    pub stats: Vec<Stats>,
+    // The last packet each NAT received.
+    pub nat: BTreeMap<i64, Packet>,
+    pub tick: u64,
+    rng: Rng,
+    capture: Option<Vec<Packet>>,
+}
+
+impl Network {
+    pub fn new(program:&Vec<i64>, config:Config) -> Network {
+        let mut nodes:Vec<Intcode> = Vec::new();
+        for address in 0..config.nodes {
+            let mut node = Intcode::new(program.clone());
+            node.input(address as i64);
+            node.run_for(config.max_ticks);
+            nodes.push(node);
+        }
+        Network {
+            rng: Rng::new(config.seed),
+            queues: vec![VecDeque::new(); config.nodes],
+            stats: vec![Stats::default(); config.nodes],
+            config: config,
+            nodes: nodes,
+            nat: BTreeMap::new(),
+            tick: 0,
+            capture: None,
+        }
+    }
+
+    // Keeps every packet routed from now on, NAT packets included.
+    pub fn set_capture(&mut self, enabled:bool) {
+        if !enabled {
+            self.capture = None;
+        } else if self.capture.is_none() {
+            self.capture = Some(Vec::new());
+        }
+    }
+
+    pub fn capture(&self) -> Option<&Vec<Packet>> {
+        self.capture.as_ref()
+    }
+
+    // Every node has halted or faulted.
+    pub fn finished(&self) -> bool {
+        self.nodes.iter().all(|node| node.status == Status::Finished || node.status == Status::Killed)
+    }
+
+    // A node is idle once it has read -1 often enough without sending or
+    // receiving anything, even if it is between two reads when its turn
+    // ran out.
+    fn idle_node(&self, address:usize) -> bool {
+        let node = &self.nodes[address];
+        match node.status {
+            Status::Blocked | Status::Running => self.queues[address].is_empty() && node.outputs.is_empty()
+                                                 && self.stats[address].idle_streak >= self.config.idle_reads,
+            _ => true,
+        }
+    }
+
+    // Every node is idle and nothing is waiting to be delivered.
+    pub fn idle(&self) -> bool {
+        (0..self.nodes.len()).all(|address| self.idle_node(address))
+    }
+
+    fn enqueue(&mut self, address:usize, x:i64, y:i64) {
+        self.queues[address].push_back(x);
+        self.queues[address].push_back(y);
+        let stats = &mut self.stats[address];
+        stats.received += 1;
+        stats.queue_depth = self.queues[address].len();
+        stats.max_queue_depth = stats.max_queue_depth.max(stats.queue_depth);
+    }
+
+    // Delivers a packet to its node or special address.
+    fn route(&mut self, packet:Packet) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.push(packet);
+        }
+        let destination = packet.destination;
+        if destination >= 0 && (destination as usize) < self.nodes.len() {
+            self.enqueue(destination as usize, packet.x, packet.y);
+            return;
+        }
+        match self.config.special.get(&destination).cloned() {
+            Some(Special::Nat { .. }) => { self.nat.insert(destination, packet); },
+            Some(Special::Broadcast) => {
+                for address in 0..self.nodes.len() {
+                    if address as i64 != packet.source {
+                        self.enqueue(address, packet.x, packet.y);
+                    }
+                }
+            },
+            Some(Special::Sink) => {},
+            None => {
+                if packet.source >= 0 && (packet.source as usize) < self.stats.len() {
+                    self.stats[packet.source as usize].dropped += 1;
+                }
+            },
+        }
+    }
+
+    // Gives a node one turn: the next value of its queue, or all of it for
+    // UntilBlocked, or -1, and a run until it blocks again or uses up its
+    // ticks, in which case it carries on next turn without input. Returns
+    // the packets it sent.
+    pub fn turn(&mut self, address:usize) -> Vec<Packet> {
+        self.tick += 1;
+        let mut packets:Vec<Packet> = Vec::new();
+        let mut value:Option<i64> = None;
+        let fed = self.nodes[address].blocked();
+        match self.nodes[address].status {
+            Status::Blocked => {
+                value = self.queues[address].pop_front();
+                self.nodes[address].input(value.unwrap_or(-1));
+                if self.config.scheduler == Scheduler::UntilBlocked {
+                    let rest:Vec<i64> = self.queues[address].drain(..).collect();
+                    self.nodes[address].append_input(&rest);
+                }
+                self.stats[address].queue_depth = self.queues[address].len();
+            },
+            Status::Running => {},
+            _ => return packets,
+        }
+        let ticks = match self.config.scheduler {
+            Scheduler::Quantum(quantum) => quantum.min(self.config.max_ticks),
+            _ => self.config.max_ticks,
+        };
+        let node = &mut self.nodes[address];
+        node.run_for(ticks);
+
+        while node.outputs.len() >= 3 {
+            let destination = node.outputs.pop_front().unwrap();
+            let x = node.outputs.pop_front().unwrap();
+            let y = node.outputs.pop_front().unwrap();
+            packets.push(Packet { source: address as i64, destination: destination, x: x, y: y, tick: self.tick });
+        }
+        let stats = &mut self.stats[address];
+        stats.sent += packets.len() as u64;
+        if value.is_some() || !packets.is_empty() {
+            stats.idle_streak = 0;
+        } else if fed {
+            stats.idle_turns += 1;
+            stats.idle_streak += 1;
+        }
+        for &packet in &packets {
+            self.route(packet);
+        }
+        packets
+    }
+
+    // Every NAT with a packet sends it on, when the network is idle.
+    pub fn wake(&mut self) -> Vec<Packet> {
+        let mut packets:Vec<Packet> = Vec::new();
+        if !self.idle() {
+            return packets;
+        }
+        for (&address, &special) in &self.config.special {
+            if let (Special::Nat { target }, Some(last)) = (special, self.nat.get(&address)) {
+                packets.push(Packet { source: address, destination: target, x: last.x, y: last.y, tick: self.tick });
+            }
+        }
+        for &packet in &packets {
+            self.route(packet);
+        }
+        packets
+    }
+
+    // One turn for every node, in the order of the scheduler, then the NATs.
+    // Returns every packet sent in the round.
+    pub fn round(&mut self) -> Vec<Packet> {
+        let mut order:Vec<usize> = (0..self.nodes.len()).collect();
+        if self.config.scheduler == Scheduler::Random {
+            for i in (1..order.len()).rev() {
+                order.swap(i, self.rng.below(i + 1));
+            }
+        }
+        let mut packets:Vec<Packet> = Vec::new();
+        for address in order {
+            packets.extend(self.turn(address));
+        }
+        packets.extend(self.wake());
+        packets
+    }
+
+    // Runs the puzzle: until a NAT sends the same y twice in a row. Returns
+    // the y of the first packet a NAT received and the repeated y.
+    pub fn nat_answers(&mut self, max_rounds:usize) -> Option<(i64, i64)> {
+        let mut first:Option<i64> = None;
+        let mut last:Option<i64> = None;
+        for _ in 0..max_rounds {
+            if self.finished() {
+                break;
+            }
+            for packet in self.round() {
+                let nat = |address| match self.config.special.get(&address) {
+                    Some(Special::Nat { .. }) => true,
+                    _ => false,
+                };
+                if nat(packet.destination) && first.is_none() {
+                    first = Some(packet.y);
+                }
+                if nat(packet.source) {
+                    if last == Some(packet.y) {
+                        return first.map(|first| (first, packet.y));
+                    }
+                    last = Some(packet.y);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn report(&self) {
+        println!("{:>7} {:>8} {:>8} {:>8} {:>6} {:>9} {:>6} {:>7}", "address", "sent", "received", "dropped", "queue", "max queue", "idle", "streak");
+        for (address, stats) in self.stats.iter().enumerate() {
+            println!("{:>7} {:>8} {:>8} {:>8} {:>6} {:>9} {:>6} {:>7}{}", address, stats.sent, stats.received, stats.dropped,
+                     stats.queue_depth, stats.max_queue_depth, stats.idle_turns, stats.idle_streak,
+                     if self.nodes[address].blocked() { String::new() } else { format!(" {:?}", self.nodes[address].status) });
+        }
+        for (address, packet) in &self.nat {
+            println!("NAT {} holds ({}, {}) from {}", address, packet.x, packet.y, packet.source);
+        }
+        println!("{} turns, {} packets sent", self.tick, self.stats.iter().map(|s| s.sent).sum::<u64>());
+    }
+}
+
+// Runs the puzzle under every scheduler, random ones with several seeds,
+// twice each, and checks that every run is reproducible and that they all
+// agree on the answers.
+pub fn compare_schedulers(program:&Vec<i64>, config:&Config, seeds:u64, max_rounds:usize) -> bool {
+    let mut policies:Vec<(Scheduler, u64)> = [Scheduler::RoundRobin, Scheduler::UntilBlocked, Scheduler::Quantum(1), Scheduler::Quantum(7), Scheduler::Quantum(100)]
+        .iter().map(|&scheduler| (scheduler, config.seed)).collect();
+    policies.extend((0..seeds).map(|i| (Scheduler::Random, config.seed.wrapping_add(i))));
+    let mut expected:Option<Option<(i64, i64)>> = None;
+    let mut passed = true;
+    for &(scheduler, seed) in &policies {
+        let run = || {
+            let mut network = Network::new(program, Config { scheduler: scheduler, seed: seed, ..config.clone() });
+            network.set_capture(true);
+            let answers = network.nat_answers(max_rounds);
+            (answers, network.tick, network.capture.take().unwrap())
+        };
+        let (answers, ticks, packets) = run();
+        let reproducible = run() == (answers, ticks, packets.clone());
+        let agrees = *expected.get_or_insert(answers) == answers;
+        println!("{:<24} {:>8} turns {:>6} packets  {:?}{}{}", format!("{:?} seed {}", scheduler, seed), ticks, packets.len(), answers,
+                 if reproducible { "" } else { "  NOT REPRODUCIBLE" }, if agrees { "" } else { "  DIFFERS" });
+        passed &= reproducible && agrees;
+    }
+    passed
+}
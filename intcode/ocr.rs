@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fmt;
+
+// The block letters of the puzzles: six rows, four columns at most, with a
+// blank column between two letters.
+const FONT:&[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+pub const HEIGHT:usize = 6;
+
+// A glyph that is not in the font: where it is among the glyphs and the
+// column of the bitmap it starts at, and how it looks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnknownGlyph {
+    pub index: usize,
+    pub column: usize,
+    pub rows: Vec<String>,
+}
+
+// The text as far as it could be read, with ? for every unknown glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OcrError {
+    pub text: String,
+    pub unknown: Vec<UnknownGlyph>,
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "read {:?}, unknown glyphs at", self.text)?;
+        for glyph in &self.unknown {
+            write!(f, " {} (column {}: {})", glyph.index + 1, glyph.column, glyph.rows.join("/"))?;
+        }
+        Ok(())
+    }
+}
+
+// Drops the blank columns on both sides of a glyph.
+fn trim(rows:&Vec<String>) -> Vec<String> {
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let used = |x:usize| rows.iter().any(|row| row.as_bytes().get(x) == Some(&b'#'));
+    let (start, end) = match ((0..width).find(|&x| used(x)), (0..width).rev().find(|&x| used(x))) {
+        (Some(start), Some(end)) => (start, end + 1),
+        _ => return rows.iter().map(|_| String::new()).collect(),
+    };
+    rows.iter().map(|row| (start..end).map(|x| if row.as_bytes().get(x) == Some(&b'#') { '#' } else { '.' }).collect()).collect()
+}
+
+// Reads the letters of a bitmap given row by row. Blank rows above and
+// below are ignored, and glyphs are told apart by the blank columns between
+// them.
+pub fn read(bitmap:&Vec<Vec<bool>>) -> Result<String, OcrError> {
+    let font:HashMap<Vec<String>, char> = FONT.iter()
+        .map(|(c, rows)| (trim(&rows.iter().map(|r| r.to_string()).collect()), *c))
+        .collect();
+    let rows:&[Vec<bool>] = match (bitmap.iter().position(|row| row.contains(&true)), bitmap.iter().rposition(|row| row.contains(&true))) {
+        (Some(first), Some(last)) => &bitmap[first..last + 1],
+        _ => &[],
+    };
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let used = |x:usize| rows.iter().any(|row| row.get(x) == Some(&true));
+
+    let mut text = String::new();
+    let mut unknown:Vec<UnknownGlyph> = Vec::new();
+    let mut x = 0;
+    while x < width {
+        if !used(x) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < width && used(x) {
+            x += 1;
+        }
+        let glyph:Vec<String> = rows.iter()
+            .map(|row| (start..x).map(|i| if row.get(i) == Some(&true) { '#' } else { '.' }).collect())
+            .collect();
+        match font.get(&glyph) {
+            Some(&c) if glyph.len() == HEIGHT => text.push(c),
+            _ => {
+                unknown.push(UnknownGlyph { index: text.chars().count(), column: start, rows: glyph });
+                text.push('?');
+            },
+        }
+    }
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError { text: text, unknown: unknown })
+    }
+}
+
+// Reads the letters drawn with `ink` in a grid, from its smallest to its
+// largest coordinates.
+pub fn read_grid<T:PartialEq>(grid:&HashMap<(i32, i32), T>, ink:&T) -> Result<String, OcrError> {
+    if grid.is_empty() {
+        return Ok(String::new());
+    }
+    let (x_coords, y_coords): (Vec<_>, Vec<_>) = grid.keys().cloned().unzip();
+    let (&x_min, &x_max) = (x_coords.iter().min().unwrap(), x_coords.iter().max().unwrap());
+    let (&y_min, &y_max) = (y_coords.iter().min().unwrap(), y_coords.iter().max().unwrap());
+    let bitmap = (y_min..y_max + 1).map(|y| (x_min..x_max + 1).map(|x| grid.get(&(x, y)) == Some(ink)).collect()).collect();
+    read(&bitmap)
+}
@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use super::analysis;
+use super::disassembler;
+use super::vm::{Instruction, Intcode, Opcode, Parameter, ParameterMode};
+
+// Equivalent programs for the given runs, smaller only where dead code can
+// be dropped. Folding constants and threading jumps rewrite instructions in
+// place and never remove a word, so they save work at run time but not
+// space. Dead code only goes when no value in the program might be an
+// address past it. Of the puzzle images only day 21 gets smaller: day 5
+// keeps its dead ranges because of such values, and days 9 and 19 have
+// none that the runs show.
+
+pub struct Optimized {
+    pub program: Vec<i64>,
+    pub safe_instructions: usize,
+    pub unsafe_instructions: usize,
+    pub folded: usize,
+    pub threaded: usize,
+    pub removed_instructions: usize,
+    pub removed_gaps: usize,
+    // Dead ranges that stay because moving the code after them would change
+    // a value that might be an address.
+    pub kept_ranges: usize,
+    pub ambiguous: BTreeSet<i64>,
+}
+
+fn opcode_number(opcode:Opcode) -> i64 {
+    match opcode {
+        Opcode::Add => 1,
+        Opcode::Multiply => 2,
+        Opcode::Input => 3,
+        Opcode::Output => 4,
+        Opcode::JumpIfTrue => 5,
+        Opcode::JumpIfFalse => 6,
+        Opcode::LessThan => 7,
+        Opcode::Equals => 8,
+        Opcode::AdjustRelativeBase => 9,
+        Opcode::Return => 99,
+        Opcode::Unknown => panic!("Unable to encode an unknown opcode"),
+    }
+}
+
+fn encode(instruction:&Instruction) -> Vec<i64> {
+    let mut words = vec![opcode_number(instruction.opcode)];
+    let mut scale = 100;
+    for parameter in instruction.parameters[..instruction.length() - 1].iter() {
+        let (mode, raw) = match *parameter {
+            Parameter::Position(address) => (0, address as i64),
+            Parameter::Immediate(value) => (1, value),
+            Parameter::Relative(offset) => (2, offset),
+            Parameter::Unknown => panic!("Unable to encode an unknown parameter"),
+        };
+        words[0] += mode * scale;
+        words.push(raw);
+        scale *= 10;
+    }
+    words
+}
+
+fn unconditional(instruction:&Instruction) -> bool {
+    match (instruction.opcode, instruction.parameters[0]) {
+        (Opcode::JumpIfTrue, Parameter::Immediate(c)) => c != 0,
+        (Opcode::JumpIfFalse, Parameter::Immediate(c)) => c == 0,
+        _ => false,
+    }
+}
+
+fn never_taken(instruction:&Instruction) -> bool {
+    match (instruction.opcode, instruction.parameters[0]) {
+        (Opcode::JumpIfTrue, Parameter::Immediate(c)) => c == 0,
+        (Opcode::JumpIfFalse, Parameter::Immediate(c)) => c != 0,
+        _ => false,
+    }
+}
+
+// The usual way a return address gets pushed: an immediate moved with add 0
+// or mul 1.
+fn code_pointer(instruction:&Instruction) -> Option<i64> {
+    match (instruction.opcode, instruction.parameters[0], instruction.parameters[1]) {
+        (Opcode::Add, Parameter::Immediate(v), Parameter::Immediate(0)) |
+        (Opcode::Add, Parameter::Immediate(0), Parameter::Immediate(v)) |
+        (Opcode::Multiply, Parameter::Immediate(v), Parameter::Immediate(1)) |
+        (Opcode::Multiply, Parameter::Immediate(1), Parameter::Immediate(v)) => Some(v),
+        _ => None,
+    }
+}
+
+// Optimizes the program under the assumption that the given runs show every
+// address it writes through a relative or computed parameter. Only
+// instructions that are never written and never read as data are changed:
+// position operands that read never written cells become immediates,
+// operations on immediates become a move of the result, branches on
+// constants become unconditional or disappear, and jumps to unconditional
+// jumps go straight to the final target. Instructions that are left
+// unreachable are removed and everything after them moves down.
+fn pass(program:&Vec<i64>, runs:&Vec<Vec<i64>>, max_ticks:usize) -> Optimized {
+    let length = program.len();
+    let original = disassembler::reachable(program);
+    let mut known = original.clone();
+    let mut written:BTreeSet<usize> = BTreeSet::new();
+    let mut read:BTreeSet<usize> = BTreeSet::new();
+    let mut executed:BTreeSet<usize> = BTreeSet::new();
+    let mut written_values:BTreeSet<i64> = BTreeSet::new();
+    let mut decoder = Intcode::new(program.clone());
+    for inputs in runs {
+        let mut computer = analysis::trace(program, inputs, max_ticks);
+        let trace = computer.memory_trace().unwrap().clone();
+        written.extend(trace.written.iter());
+        read.extend(trace.read.iter());
+        for &address in trace.instructions.iter().filter(|&&a| a < length) {
+            executed.insert(address);
+            if known.contains_key(&address) {
+                continue;
+            }
+            // An instruction whose operands run past the end of the image
+            // reads the zeros after it. It is left as it is, like
+            // disassembler::reachable leaves it out.
+            let instruction = decoder.decode(address);
+            if address + instruction.length() > length {
+                read.extend(address..length);
+            } else {
+                known.insert(address, instruction);
+            }
+        }
+        written_values.extend(trace.self_modifications.iter().map(|m| m.value));
+        for address in trace.written.iter().filter(|&&a| a < length) {
+            written_values.insert(computer.read(*address));
+        }
+    }
+
+    // Every cell a known instruction names with a position operand.
+    let mut owners:BTreeMap<usize, usize> = BTreeMap::new();
+    let mut overlapping:BTreeSet<usize> = BTreeSet::new();
+    for (&address, instruction) in &known {
+        for word in address..address + instruction.length() {
+            if let Some(other) = owners.insert(word, address) {
+                overlapping.insert(other);
+                overlapping.insert(address);
+            }
+        }
+        for (i, parameter) in instruction.parameters[..instruction.length() - 1].iter().enumerate() {
+            if let Parameter::Position(cell) = *parameter {
+                match (instruction.opcode, i) {
+                    (Opcode::Input, 0) | (Opcode::Add, 2) | (Opcode::Multiply, 2) | (Opcode::LessThan, 2) | (Opcode::Equals, 2) => written.insert(cell),
+                    _ => read.insert(cell),
+                };
+            }
+        }
+    }
+    let safe:BTreeSet<usize> = known.iter()
+                                    .filter(|(a, _)| !overlapping.contains(a))
+                                    .filter(|(&a, i)| (a..a + i.length()).all(|w| !written.contains(&w) && !read.contains(&w)))
+                                    .map(|(&a, _)| a)
+                                    .collect();
+
+    let constant = |parameter:Parameter| match parameter {
+        Parameter::Immediate(value) => Some(value),
+        Parameter::Position(cell) if cell < length && !written.contains(&cell) => Some(program[cell]),
+        _ => None,
+    };
+    let mut rewritten = known.clone();
+    let mut folded = 0;
+    for &address in safe.iter().filter(|a| executed.contains(a)) {
+        let before = rewritten[&address];
+        let mut instruction = before;
+        let operands = match instruction.opcode {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals | Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Output | Opcode::AdjustRelativeBase => 1,
+            _ => 0,
+        };
+        for i in 0..operands {
+            if let (Parameter::Position(_), Some(value)) = (instruction.parameters[i], constant(instruction.parameters[i])) {
+                instruction.parameters[i] = Parameter::Immediate(value);
+                instruction.modes[i] = ParameterMode::Immediate;
+            }
+        }
+        if let (Parameter::Immediate(a), Parameter::Immediate(b), None) = (instruction.parameters[0], instruction.parameters[1], code_pointer(&instruction)) {
+            let value = match instruction.opcode {
+                Opcode::Add => Some(a.wrapping_add(b)),
+                Opcode::Multiply => Some(a.wrapping_mul(b)),
+                Opcode::LessThan => Some((a < b) as i64),
+                Opcode::Equals => Some((a == b) as i64),
+                _ => None,
+            };
+            if let Some(value) = value {
+                instruction.opcode = Opcode::Add;
+                instruction.parameters[0] = Parameter::Immediate(value);
+                instruction.parameters[1] = Parameter::Immediate(0);
+            }
+        }
+        if instruction == before {
+            continue;
+        }
+        if unconditional(&instruction) {
+            instruction.opcode = Opcode::JumpIfTrue;
+            instruction.parameters[0] = Parameter::Immediate(1);
+        }
+        rewritten.insert(address, instruction);
+        folded += 1;
+    }
+
+    let mut threaded = 0;
+    for &address in safe.iter().filter(|a| executed.contains(a)) {
+        let mut instruction = rewritten[&address];
+        let mut target = match (instruction.opcode, instruction.parameters[1]) {
+            (Opcode::JumpIfTrue, Parameter::Immediate(t)) | (Opcode::JumpIfFalse, Parameter::Immediate(t)) if t >= 0 => t as usize,
+            _ => continue,
+        };
+        let mut visited:BTreeSet<usize> = BTreeSet::new();
+        while visited.insert(target) && safe.contains(&target) && unconditional(&rewritten[&target]) {
+            match rewritten[&target].parameters[1] {
+                Parameter::Immediate(next) if next >= 0 => target = next as usize,
+                _ => break,
+            }
+        }
+        if Parameter::Immediate(target as i64) != instruction.parameters[1] {
+            instruction.parameters[1] = Parameter::Immediate(target as i64);
+            rewritten.insert(address, instruction);
+            threaded += 1;
+        }
+        if unconditional(&instruction) && target == address + instruction.length() {
+            instruction.opcode = Opcode::JumpIfTrue;
+            instruction.parameters[0] = Parameter::Immediate(0);
+            rewritten.insert(address, instruction);
+        }
+    }
+
+    let mut image = program.clone();
+    for (&address, instruction) in &rewritten {
+        if *instruction != known[&address] {
+            for (i, word) in encode(instruction).into_iter().enumerate() {
+                image[address + i] = word;
+            }
+        }
+    }
+    let mut live:BTreeSet<usize> = disassembler::reachable(&image).keys().cloned().collect();
+    live.extend(executed.iter().filter(|a| !original.contains_key(a)));
+
+    // Dead ranges by start address: instructions that can no longer be
+    // reached or never jump, plus the words right after an instruction that
+    // never falls through that nothing reads, writes or points to (what a
+    // compiler leaves behind a return).
+    let mut dead:BTreeMap<usize, usize> = safe.iter()
+                                              .filter(|&&a| original.contains_key(&a))
+                                              .filter(|a| !live.contains(a) || never_taken(&rewritten[a]))
+                                              .map(|&a| (a, rewritten[&a].length()))
+                                              .collect();
+    let mut covered = vec![false; length];
+    for (&address, instruction) in &known {
+        for word in address..(address + instruction.length()).min(length) {
+            covered[word] = true;
+        }
+    }
+    let mut gaps:Vec<(usize, usize)> = Vec::new();
+    for (&address, instruction) in &rewritten {
+        let falls_through = match instruction.opcode {
+            Opcode::Return => false,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => !unconditional(instruction),
+            _ => true,
+        };
+        let start = address + instruction.length();
+        if falls_through || !live.contains(&address) {
+            continue;
+        }
+        let mut end = start;
+        while end < length && !covered[end] && !read.contains(&end) && !written.contains(&end) {
+            end += 1;
+        }
+        if end > start && end < length {
+            gaps.push((start, end - start));
+        }
+    }
+    let in_gap = |address:usize| gaps.iter().any(|&(start, size)| address >= start && address < start + size);
+    let mut pointers:BTreeSet<i64> = written_values.clone();
+    for (_, instruction) in known.iter().filter(|(a, _)| !dead.contains_key(a)) {
+        for parameter in instruction.parameters[..instruction.length() - 1].iter() {
+            if let Parameter::Immediate(value) = *parameter {
+                pointers.insert(value);
+            }
+        }
+    }
+    pointers.extend((0..length).filter(|&a| !covered[a] && !in_gap(a)).map(|a| program[a]));
+    for &(start, size) in &gaps {
+        if pointers.range(start as i64..(start + size) as i64).next().is_none() {
+            dead.insert(start, size);
+        }
+    }
+
+    // Moving code down changes every address after the first removal. Jump
+    // targets, position operands and pushed return addresses are rewritten;
+    // any other value in that range might be an address computed at run
+    // time, so removals in front of it are given up.
+    let mut ambiguous:BTreeSet<i64> = BTreeSet::new();
+    let mut kept_ranges = 0;
+    loop {
+        let first = match dead.keys().next() {
+            Some(&first) => first as i64,
+            None => break,
+        };
+        let mut removed = vec![false; length];
+        for (&start, &size) in &dead {
+            for word in start..start + size {
+                removed[word] = true;
+            }
+        }
+        let mut candidates:BTreeSet<i64> = written_values.clone();
+        let mut kept = vec![false; length];
+        for (&address, instruction) in rewritten.iter().filter(|(a, _)| !dead.contains_key(a)) {
+            for word in address..(address + instruction.length()).min(length) {
+                kept[word] = true;
+            }
+            let pointer = code_pointer(instruction).filter(|v| *v >= 0 && live.contains(&(*v as usize)));
+            let immediates = match instruction.opcode {
+                Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 2,
+                Opcode::AdjustRelativeBase => 1,
+                _ => 0,
+            };
+            for parameter in instruction.parameters[..immediates].iter() {
+                if let Parameter::Immediate(value) = *parameter {
+                    if pointer != Some(value) {
+                        candidates.insert(value);
+                    }
+                }
+            }
+        }
+        candidates.extend((0..length).filter(|&a| !kept[a] && !removed[a]).map(|a| image[a]));
+        let blocking:Vec<i64> = candidates.into_iter().filter(|&v| v > first && v < length as i64).collect();
+        match blocking.last() {
+            Some(&cutoff) => {
+                ambiguous.extend(blocking.iter().cloned());
+                let given_up:Vec<usize> = dead.keys().filter(|&&d| (d as i64) < cutoff).cloned().collect();
+                kept_ranges += given_up.len();
+                for d in given_up {
+                    dead.remove(&d);
+                }
+            },
+            None => break,
+        }
+    }
+
+    let mut removed = vec![false; length];
+    for (&start, &size) in &dead {
+        for word in start..start + size {
+            removed[word] = true;
+        }
+    }
+    let mut shift = vec![0; length + 1];
+    for address in 0..length {
+        shift[address + 1] = shift[address] + removed[address] as i64;
+    }
+    let relocate = |value:i64| if value >= 0 && value < length as i64 { value - shift[value as usize] } else { value };
+
+    for (&address, instruction) in rewritten.iter().filter(|(a, _)| !removed[**a]) {
+        let pointer = code_pointer(instruction).filter(|v| *v >= 0 && live.contains(&(*v as usize)));
+        for (i, parameter) in instruction.parameters[..instruction.length() - 1].iter().enumerate() {
+            let word = address + i + 1;
+            match (*parameter, instruction.opcode, i) {
+                (Parameter::Position(_), _, _) |
+                (Parameter::Immediate(_), Opcode::JumpIfTrue, 1) |
+                (Parameter::Immediate(_), Opcode::JumpIfFalse, 1) => image[word] = relocate(image[word]),
+                (Parameter::Immediate(value), _, _) if pointer == Some(value) && i < 2 => image[word] = relocate(value),
+                _ => {},
+            }
+        }
+    }
+
+    let removed_instructions = dead.keys().filter(|a| known.contains_key(a)).count();
+    Optimized {
+        program: image.into_iter().enumerate().filter(|&(a, _)| !removed[a]).map(|(_, w)| w).collect(),
+        safe_instructions: safe.len(),
+        unsafe_instructions: known.len() - safe.len(),
+        folded: folded,
+        threaded: threaded,
+        removed_instructions: removed_instructions,
+        removed_gaps: dead.len() - removed_instructions,
+        kept_ranges: kept_ranges,
+        ambiguous: ambiguous,
+    }
+}
+
+// Removing code can leave a jump to the very next instruction behind, so
+// passes repeat for as long as the program keeps shrinking.
+pub fn optimize(program:&Vec<i64>, runs:&Vec<Vec<i64>>, max_ticks:usize) -> Optimized {
+    let mut optimized = pass(program, runs, max_ticks);
+    loop {
+        if optimized.program.len() == program.len() {
+            return optimized;
+        }
+        let mut next = pass(&optimized.program, runs, max_ticks);
+        if next.program.len() == optimized.program.len() {
+            return optimized;
+        }
+        next.safe_instructions = optimized.safe_instructions;
+        next.unsafe_instructions = optimized.unsafe_instructions;
+        next.folded += optimized.folded;
+        next.threaded += optimized.threaded;
+        next.removed_instructions += optimized.removed_instructions;
+        next.removed_gaps += optimized.removed_gaps;
+        optimized = next;
+    }
+}
+
+pub fn report(program:&Vec<i64>, optimized:&Optimized) {
+    println!("Instructions: {} safe to change, {} written or read as data", optimized.safe_instructions, optimized.unsafe_instructions);
+    println!("Folded: {}, threaded jumps: {}, removed: {} instructions and {} unreachable gaps",
+             optimized.folded, optimized.threaded, optimized.removed_instructions, optimized.removed_gaps);
+    if optimized.kept_ranges > 0 {
+        let values:Vec<String> = optimized.ambiguous.iter().take(10).map(|v| v.to_string()).collect();
+        println!("Kept {} dead ranges because of values that might be addresses: {}{}",
+                 optimized.kept_ranges, values.join(","), if optimized.ambiguous.len() > 10 { ",..." } else { "" });
+    }
+    println!("Size: {} -> {} words", program.len(), optimized.program.len());
+}
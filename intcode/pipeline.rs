@@ -0,0 +1,269 @@
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::vm::{Intcode, Status};
+
+// How a node with several producers takes their values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Merge {
+    // In the order the values were produced.
+    Arrival,
+    // One value from each producer in turn, waiting for the next producer
+    // even when the others have values.
+    Alternate,
+    // One value from every producer at once, fed as their sum, which wraps
+    // around like the VM's additions.
+    Sum,
+    // One value from every producer at once, fed as the largest.
+    Max,
+}
+
+impl Merge {
+    pub fn parse(text:&str) -> Result<Merge, String> {
+        match text {
+            "arrival" => Ok(Merge::Arrival),
+            "alternate" => Ok(Merge::Alternate),
+            "sum" => Ok(Merge::Sum),
+            "max" => Ok(Merge::Max),
+            _ => Err(format!("unknown merge policy {:?}, expected arrival, alternate, sum or max", text)),
+        }
+    }
+}
+
+// Why a pipeline stopped before every node halted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stop {
+    // No node can make progress; these nodes wait for input that never
+    // comes.
+    Deadlock(Vec<String>),
+    // A node faulted.
+    Killed(String),
+    // The nodes ran this many instructions in total without halting.
+    Timeout(usize),
+    // There were no nodes to run.
+    Empty,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Deadlock(nodes) => write!(f, "deadlock: {} stalled waiting for input", nodes.join(", ")),
+            Stop::Killed(node) => write!(f, "{} was killed", node),
+            Stop::Timeout(ticks) => write!(f, "no result after {} instructions", ticks),
+            Stop::Empty => write!(f, "no amplifiers to run"),
+        }
+    }
+}
+
+struct Node {
+    name: String,
+    computer: Intcode,
+    merge: Merge,
+    // The nodes this one reads from, with what each has sent and not been
+    // taken yet.
+    producers: Vec<(usize, VecDeque<i64>)>,
+    // Which producer every waiting value came from, for Arrival.
+    arrivals: VecDeque<usize>,
+    // The producer Alternate takes from next.
+    next: usize,
+    // The nodes this one sends to, with the index of the edge in their
+    // producers.
+    consumers: Vec<(usize, usize)>,
+    output: bool,
+}
+
+// Wires Intcode computers into a graph: every value a node outputs is copied
+// to each of its consumers and, for output nodes, to the pipeline's outputs.
+// Day 7's amplifiers are a chain, with a feedback edge from the last to the
+// first in part 2.
+pub struct Builder {
+    nodes: Vec<Node>,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder { nodes: Vec::new() }
+    }
+
+    // Adds a node running the program, which reads its initial inputs, such
+    // as a phase setting, before anything its producers send. Returns its
+    // index.
+    pub fn node(&mut self, name:&str, program:&Vec<i64>, initial:&[i64]) -> usize {
+        let mut computer = Intcode::new(program.clone());
+        computer.append_input(&initial.to_vec());
+        self.nodes.push(Node {
+            name: name.to_string(),
+            computer: computer,
+            merge: Merge::Arrival,
+            producers: Vec::new(),
+            arrivals: VecDeque::new(),
+            next: 0,
+            consumers: Vec::new(),
+            output: false,
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn input(&mut self, node:usize, value:i64) -> &mut Builder {
+        self.nodes[node].computer.input(value);
+        self
+    }
+
+    pub fn connect(&mut self, from:usize, to:usize) -> &mut Builder {
+        let edge = self.nodes[to].producers.len();
+        self.nodes[from].consumers.push((to, edge));
+        self.nodes[to].producers.push((from, VecDeque::new()));
+        self
+    }
+
+    // Connects every node to the next one.
+    pub fn chain(&mut self, nodes:&[usize]) -> &mut Builder {
+        for pair in nodes.windows(2) {
+            self.connect(pair[0], pair[1]);
+        }
+        self
+    }
+
+    pub fn fan_out(&mut self, from:usize, to:&[usize]) -> &mut Builder {
+        for &node in to {
+            self.connect(from, node);
+        }
+        self
+    }
+
+    pub fn fan_in(&mut self, from:&[usize], to:usize, merge:Merge) -> &mut Builder {
+        for &node in from {
+            self.connect(node, to);
+        }
+        self.merge(to, merge)
+    }
+
+    pub fn merge(&mut self, node:usize, merge:Merge) -> &mut Builder {
+        self.nodes[node].merge = merge;
+        self
+    }
+
+    pub fn output(&mut self, node:usize) -> &mut Builder {
+        self.nodes[node].output = true;
+        self
+    }
+
+    pub fn build(&mut self) -> Pipeline {
+        Pipeline { nodes: std::mem::replace(&mut self.nodes, Vec::new()), outputs: Vec::new(), ticks: 0 }
+    }
+
+    // Amplifiers named A, B, C... one per phase, in a chain fed a single 0,
+    // with the last one as output and, for a feedback loop, also feeding
+    // the first. Past Z they are numbered instead.
+    pub fn amplifiers(program:&Vec<i64>, phases:&[i64], feedback:bool) -> Result<Pipeline, Stop> {
+        let mut builder = Builder::new();
+        let nodes:Vec<usize> = phases.iter().enumerate().map(|(i, &phase)| {
+            let name = if i < 26 { ((b'A' + i as u8) as char).to_string() } else { format!("#{}", i + 1) };
+            builder.node(&name, program, &[phase])
+        }).collect();
+        let (&first, &last) = match (nodes.first(), nodes.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(Stop::Empty),
+        };
+        builder.chain(&nodes).input(first, 0).output(last);
+        if feedback {
+            builder.connect(last, first);
+        }
+        Ok(builder.build())
+    }
+}
+
+pub struct Pipeline {
+    nodes: Vec<Node>,
+    // Everything the output nodes produced, in order.
+    pub outputs: Vec<i64>,
+    pub ticks: usize,
+}
+
+impl Pipeline {
+    pub fn name(&self, node:usize) -> &str {
+        &self.nodes[node].name
+    }
+
+    pub fn status(&self, node:usize) -> &Status {
+        &self.nodes[node].computer.status
+    }
+
+    // Moves what a node's producers sent into its input, as far as its
+    // merge policy allows.
+    fn feed(&mut self, index:usize) {
+        let node = &mut self.nodes[index];
+        if node.producers.is_empty() {
+            return;
+        }
+        match node.merge {
+            Merge::Arrival => {
+                while let Some(producer) = node.arrivals.pop_front() {
+                    let value = node.producers[producer].1.pop_front().unwrap();
+                    node.computer.input(value);
+                }
+            },
+            Merge::Alternate => {
+                while let Some(value) = node.producers[node.next].1.pop_front() {
+                    node.computer.input(value);
+                    node.next = (node.next + 1) % node.producers.len();
+                }
+            },
+            Merge::Sum | Merge::Max => {
+                while node.producers.iter().all(|(_, queue)| !queue.is_empty()) {
+                    let values = node.producers.iter_mut().map(|(_, queue)| queue.pop_front().unwrap());
+                    let value = if node.merge == Merge::Sum { values.fold(0i64, i64::wrapping_add) } else { values.max().unwrap() };
+                    node.computer.input(value);
+                }
+            },
+        }
+    }
+
+    fn deliver(&mut self, from:usize, value:i64) {
+        if self.nodes[from].output {
+            self.outputs.push(value);
+        }
+        for i in 0..self.nodes[from].consumers.len() {
+            let (to, edge) = self.nodes[from].consumers[i];
+            let consumer = &mut self.nodes[to];
+            consumer.producers[edge].1.push_back(value);
+            if consumer.merge == Merge::Arrival {
+                consumer.arrivals.push_back(edge);
+            }
+        }
+    }
+
+    // Gives every node in turn up to `slice` instructions, until they have
+    // all halted, one is killed, no node can go on or `max_ticks` ran in
+    // total.
+    pub fn run(&mut self, slice:usize, max_ticks:usize) -> Result<&Vec<i64>, Stop> {
+        loop {
+            let mut progress = false;
+            for index in 0..self.nodes.len() {
+                self.feed(index);
+                let ticks = self.nodes[index].computer.run_for(slice);
+                self.ticks += ticks;
+                progress |= ticks > 0;
+                let outputs:Vec<i64> = self.nodes[index].computer.outputs.drain(..).collect();
+                for value in outputs {
+                    self.deliver(index, value);
+                }
+                if self.nodes[index].computer.status == Status::Killed {
+                    return Err(Stop::Killed(self.nodes[index].name.clone()));
+                }
+            }
+            if self.nodes.iter().all(|node| node.computer.finished()) {
+                return Ok(&self.outputs);
+            }
+            if !progress {
+                let stalled = self.nodes.iter().filter(|node| node.computer.blocked()).map(|node| node.name.clone()).collect();
+                return Err(Stop::Deadlock(stalled));
+            }
+            if self.ticks >= max_ticks {
+                return Err(Stop::Timeout(self.ticks));
+            }
+        }
+    }
+}
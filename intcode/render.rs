@@ -0,0 +1,211 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use super::cast::Cast;
+
+// Milliseconds between the frames of a recording, unless told otherwise.
+const DEFAULT_FRAME_INTERVAL:u64 = 40;
+
+// How each kind of tile looks: a glyph, all of the same width, and a
+// foreground color from the 256 color palette.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    tiles: HashMap<u8, (String, Option<u8>)>,
+    width: usize,
+    blank: String,
+}
+
+impl Theme {
+    // Tiles without a glyph of their own are drawn blank.
+    pub fn new(width:usize) -> Theme {
+        Theme { tiles: HashMap::new(), width: width, blank: " ".repeat(width) }
+    }
+
+    pub fn tile(mut self, tile:u8, glyph:&str, color:Option<u8>) -> Theme {
+        assert_eq!(glyph.chars().count(), self.width, "Glyph {:?} is not {} wide", glyph, self.width);
+        self.tiles.insert(tile, (glyph.to_string(), color));
+        self
+    }
+
+    pub fn glyph(&self, tile:u8) -> &str {
+        self.tiles.get(&tile).map_or(&self.blank, |(glyph, _)| glyph)
+    }
+
+    pub fn color(&self, tile:u8) -> Option<u8> {
+        self.tiles.get(&tile).and_then(|&(_, color)| color)
+    }
+}
+
+// What a screen shows: the last frame written to it and its status line.
+#[derive(Default)]
+struct Canvas {
+    previous: Vec<Vec<u8>>,
+    status: String,
+}
+
+impl Canvas {
+    // The escape sequences that turn the previous frame into this one: the
+    // changed cells with the cursor moved to them, or everything after a
+    // clear when the size changed.
+    fn update(&mut self, theme:&Theme, colors:bool, frame:&Vec<Vec<u8>>, status:&str) -> String {
+        let mut out = String::new();
+        let resized = frame.len() != self.previous.len() || frame.iter().zip(&self.previous).any(|(a, b)| a.len() != b.len());
+        if resized {
+            out.push_str("\x1b[2J");
+        }
+        // Where the cursor is and the color in use, so that runs of changed
+        // cells need neither moves nor color changes in between.
+        let mut cursor:Option<(usize, usize)> = None;
+        let mut color:Option<Option<u8>> = None;
+        for (y, row) in frame.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if !resized && self.previous[y][x] == tile {
+                    continue;
+                }
+                if cursor != Some((y, x)) {
+                    out.push_str(&format!("\x1b[{};{}H", y + 1, x * theme.width + 1));
+                }
+                let tile_color = if colors { theme.color(tile) } else { None };
+                if color != Some(tile_color) {
+                    match tile_color {
+                        Some(c) => out.push_str(&format!("\x1b[38;5;{}m", c)),
+                        None => out.push_str("\x1b[0m"),
+                    }
+                    color = Some(tile_color);
+                }
+                out.push_str(theme.glyph(tile));
+                cursor = Some((y, x + 1));
+            }
+        }
+        if color.map_or(false, |c| c.is_some()) {
+            out.push_str("\x1b[0m");
+        }
+        if resized || status != self.status {
+            out.push_str(&format!("\x1b[{};1H{}\x1b[K", frame.len() + 1, status));
+            self.status = status.to_string();
+        }
+        self.previous = frame.clone();
+        out
+    }
+}
+
+// Draws frames of a grid of tiles in place. On a terminal only the cells
+// that changed since the previous frame are written, and a frame that comes
+// sooner than the frame rate allows is kept back until the next one or
+// `finish`. When stdout is not a terminal nothing is drawn until `finish`,
+// which prints the last frame as plain text. A recording, if any, gets every
+// frame.
+pub struct Renderer {
+    theme: Theme,
+    tty: bool,
+    colors: bool,
+    interval: Duration,
+    last_drawn: Option<Instant>,
+    display: Canvas,
+    pending: Option<(Vec<Vec<u8>>, String)>,
+    recording: Option<(Canvas, Cast, String)>,
+}
+
+impl Renderer {
+    pub fn new(theme:Theme) -> Renderer {
+        let tty = io::stdout().is_terminal();
+        Renderer {
+            theme: theme,
+            tty: tty,
+            colors: env::var_os("NO_COLOR").is_none(),
+            interval: Duration::from_secs(0),
+            last_drawn: None,
+            display: Canvas::default(),
+            pending: None,
+            recording: None,
+        }
+    }
+
+    pub fn is_tty(&self) -> bool {
+        self.tty
+    }
+
+    // At most this many frames a second, or any number for 0.
+    pub fn set_max_fps(&mut self, fps:u32) {
+        self.interval = if fps == 0 { Duration::from_secs(0) } else { Duration::from_secs(1) / fps };
+    }
+
+    // Records every frame from now on as an asciicast, saved to the path by
+    // `finish`.
+    pub fn record(&mut self, path:&str, frame_interval:Option<Duration>) {
+        self.recording = Some((Canvas::default(), Cast::new(frame_interval), path.to_string()));
+    }
+
+    // Shows the frame with a status line below it, unless it is held back.
+    // Returns whether it was drawn.
+    pub fn draw(&mut self, frame:Vec<Vec<u8>>, status:&str) -> bool {
+        if let Some((canvas, cast, _)) = self.recording.as_mut() {
+            let width = self.theme.width;
+            let columns = frame.iter().map(|row| row.len() * width).max().unwrap_or(0).max(status.chars().count());
+            cast.frame(canvas.update(&self.theme, self.colors, &frame, status), columns, frame.len() + 2);
+        }
+        let due = self.last_drawn.map_or(true, |last| last.elapsed() >= self.interval);
+        if !self.tty || !due {
+            self.pending = Some((frame, status.to_string()));
+            return false;
+        }
+        self.pending = None;
+        self.show(&frame, status);
+        self.last_drawn = Some(Instant::now());
+        true
+    }
+
+    // Draws the frame held back last, if any, leaves the cursor below the
+    // grid and saves the recording.
+    pub fn finish(&mut self) {
+        let pending = self.pending.take();
+        if let Some((_, cast, path)) = self.recording.take() {
+            cast.save(&path);
+        }
+        if !self.tty {
+            if let Some((frame, status)) = pending {
+                for row in &frame {
+                    println!("{}", row.iter().map(|&tile| self.theme.glyph(tile)).collect::<String>());
+                }
+                if !status.is_empty() {
+                    println!("{}", status);
+                }
+            }
+            return;
+        }
+        if let Some((frame, status)) = pending {
+            self.show(&frame, &status);
+        }
+        print!("\x1b[{};1H", self.display.previous.len() + 2);
+        io::stdout().flush().unwrap();
+    }
+
+    fn show(&mut self, frame:&Vec<Vec<u8>>, status:&str) {
+        print!("{}", self.display.update(&self.theme, self.colors, frame, status));
+        io::stdout().flush().unwrap();
+    }
+}
+
+// Takes the cast=<file> and interval=<ms> options out of the arguments: the
+// file to record an asciicast to and the time between its frames, or the
+// time they were drawn for interval=0.
+pub fn cast_options(args:&mut Vec<String>) -> Option<(String, Option<Duration>)> {
+    let mut path:Option<String> = None;
+    let mut interval = Some(Duration::from_millis(DEFAULT_FRAME_INTERVAL));
+    args.retain(|arg| {
+        if arg.starts_with("cast=") {
+            path = Some(arg["cast=".len()..].to_string());
+        } else if arg.starts_with("interval=") {
+            let ms:u64 = arg["interval=".len()..].parse().expect("Expected interval=<ms>");
+            interval = if ms == 0 { None } else { Some(Duration::from_millis(ms)) };
+        } else {
+            return true;
+        }
+        false
+    });
+    path.map(|path| (path, interval))
+}
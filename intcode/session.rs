@@ -0,0 +1,145 @@
+use std::fs;
+
+use super::vm::{Intcode, Recording};
+
+// A recorded I/O session: the program it ran, the words patched into memory
+// before the run (such as the quarters of day 13), every input with the
+// number of outputs produced before it was consumed, and the outputs and
+// status the run ended with. The hash is that of the program before the
+// patches. Saved as text, one entry per line:
+//
+//     program 8d3a5c1f0e6b2a47
+//     patch 0 2
+//     status Finished
+//     input 0 110
+//     input 0 111
+//     outputs 10,61,61
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub program_hash: u64,
+    pub patches: Vec<(usize, i64)>,
+    pub status: String,
+    pub inputs: Vec<(usize, i64)>,
+    pub outputs: Vec<i64>,
+}
+
+// FNV-1a over the words of the program.
+pub fn program_hash(program:&Vec<i64>) -> u64 {
+    let mut hash:u64 = 0xcbf2_9ce4_8422_2325;
+    for word in program {
+        for byte in word.to_le_bytes().iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+    hash
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session { program_hash: 0, patches: Vec::new(), status: String::new(), inputs: Vec::new(), outputs: Vec::new() }
+    }
+
+    // The session of a computer that has been recording since it started
+    // running `program` with the patches applied.
+    pub fn from_run(program:&Vec<i64>, patches:&[(usize, i64)], computer:&Intcode) -> Session {
+        let recording = computer.recording().expect("Recording is not enabled");
+        Session {
+            program_hash: program_hash(program),
+            patches: patches.to_vec(),
+            status: format!("{:?}", computer.status),
+            inputs: recording.inputs.clone(),
+            outputs: recording.outputs.clone(),
+        }
+    }
+
+    pub fn save(&self, path:&str) {
+        let mut text = format!("program {:016x}\n", self.program_hash);
+        for (address, value) in &self.patches {
+            text.push_str(&format!("patch {} {}\n", address, value));
+        }
+        text.push_str(&format!("status {}\n", self.status));
+        for (position, value) in &self.inputs {
+            text.push_str(&format!("input {} {}\n", position, value));
+        }
+        text.push_str(&format!("outputs {}\n", self.outputs.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")));
+        fs::write(path, text).expect("Unable to write file");
+    }
+
+    pub fn load(path:&str) -> Result<Session, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut session = Session::new();
+        for (n, line) in text.lines().enumerate() {
+            let error = || format!("{}: line {}: unable to parse {:?}", path, n + 1, line);
+            let mut fields = line.splitn(2, ' ');
+            match (fields.next(), fields.next()) {
+                (Some("program"), Some(hash)) => session.program_hash = u64::from_str_radix(hash.trim(), 16).map_err(|_| error())?,
+                (Some("status"), Some(status)) => session.status = status.trim().to_string(),
+                (Some("patch"), Some(patch)) => {
+                    let values:Vec<&str> = patch.split_whitespace().collect();
+                    match (values.get(0).and_then(|a| a.parse().ok()), values.get(1).and_then(|v| v.parse().ok())) {
+                        (Some(address), Some(value)) => session.patches.push((address, value)),
+                        _ => return Err(error()),
+                    }
+                },
+                (Some("input"), Some(input)) => {
+                    let values:Vec<&str> = input.split_whitespace().collect();
+                    match (values.get(0).and_then(|p| p.parse().ok()), values.get(1).and_then(|v| v.parse().ok())) {
+                        (Some(position), Some(value)) => session.inputs.push((position, value)),
+                        _ => return Err(error()),
+                    }
+                },
+                (Some("outputs"), outputs) => {
+                    session.outputs = outputs.unwrap_or("").split(',').filter(|v| !v.trim().is_empty())
+                                             .map(|v| v.trim().parse().map_err(|_| error())).collect::<Result<_, _>>()?;
+                },
+                (Some(""), None) => {},
+                _ => return Err(error()),
+            }
+        }
+        Ok(session)
+    }
+
+    // Runs the program again, with the recorded patches applied, feeding
+    // each input only once the run has produced as many outputs as when it
+    // was recorded, and checks that the replay ends with the same outputs
+    // and status.
+    pub fn replay(&self, program:&Vec<i64>) -> Result<(), String> {
+        if program_hash(program) != self.program_hash {
+            return Err(format!("the session was recorded with another program (hash {:016x}, this one is {:016x})",
+                               self.program_hash, program_hash(program)));
+        }
+        let mut patched = program.clone();
+        for &(address, value) in &self.patches {
+            if address >= patched.len() {
+                return Err(format!("the session patches address {}, past the end of the program ({} words)", address, patched.len()));
+            }
+            patched[address] = value;
+        }
+        let mut computer = Intcode::new(patched);
+        computer.set_recording(true);
+        computer.execute();
+        for (i, &(position, value)) in self.inputs.iter().enumerate() {
+            let produced = computer.recording().unwrap().outputs.len();
+            if !computer.blocked() {
+                return Err(format!("the program stopped ({:?}) after {} outputs, before consuming input {} of {}",
+                                   computer.status, produced, i + 1, self.inputs.len()));
+            }
+            if produced != position {
+                return Err(format!("input {} was consumed after {} outputs when recorded, but after {} in the replay", i + 1, position, produced));
+            }
+            computer.outputs.clear();
+            computer.input(value);
+            computer.r#continue();
+        }
+        let Recording { outputs, .. } = computer.recording().unwrap();
+        if let Some(i) = (0..outputs.len().max(self.outputs.len())).find(|&i| outputs.get(i) != self.outputs.get(i)) {
+            return Err(format!("output {} is {:?}, recorded {:?} ({} outputs in the replay, {} recorded)",
+                               i + 1, outputs.get(i), self.outputs.get(i), outputs.len(), self.outputs.len()));
+        }
+        if format!("{:?}", computer.status) != self.status {
+            return Err(format!("the replay ends {:?}, recorded {}", computer.status, self.status));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+use super::vm::{Intcode, Opcode, ParameterMode, Status};
+
+// Values computed in a loop over a symbol grow with every iteration, so a
+// path that builds anything larger is abandoned.
+const MAX_EXPRESSION_SIZE:usize = 256;
+
+// Symbolic values. Sums and scalings of symbols are kept in linear normal
+// form (a constant plus a coefficient per symbol); anything else becomes an
+// expression tree that the solver can only evaluate, not invert.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Expr {
+    Linear(i64, BTreeMap<usize, i64>),
+    Sum(Box<Expr>, Box<Expr>),
+    Product(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+    // A read through an address that depends on a symbol. Its value is not
+    // tracked, so candidate solutions are confirmed by running the program.
+    Load(Box<Expr>),
+    // Whatever a path would have produced after exploration gave up on it.
+    Unknown,
+}
+
+impl Expr {
+    pub fn constant(value:i64) -> Expr {
+        Expr::Linear(value, BTreeMap::new())
+    }
+
+    pub fn symbol(symbol:usize) -> Expr {
+        let mut terms:BTreeMap<usize, i64> = BTreeMap::new();
+        terms.insert(symbol, 1);
+        Expr::Linear(0, terms)
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self {
+            Expr::Linear(value, terms) if terms.is_empty() => Some(*value),
+            _ => None,
+        }
+    }
+
+    // Arithmetic wraps around like the VM's, so that an expression has the
+    // value the program computes.
+    fn scale(&self, factor:i64) -> Option<Expr> {
+        match self {
+            Expr::Linear(constant, terms) => {
+                let terms = terms.iter().map(|(&s, &c)| (s, c.wrapping_mul(factor))).filter(|&(_, c)| c != 0).collect();
+                Some(Expr::Linear(constant.wrapping_mul(factor), terms))
+            },
+            _ => None,
+        }
+    }
+
+    pub fn add(a:Expr, b:Expr) -> Expr {
+        match (a, b) {
+            (Expr::Linear(c1, t1), Expr::Linear(c2, t2)) => {
+                let mut terms = t1;
+                for (s, c) in t2 {
+                    let term = terms.entry(s).or_insert(0);
+                    *term = term.wrapping_add(c);
+                }
+                terms.retain(|_, c| *c != 0);
+                Expr::Linear(c1.wrapping_add(c2), terms)
+            },
+            (a, b) => {
+                if a.as_constant() == Some(0) {
+                    b
+                } else if b.as_constant() == Some(0) {
+                    a
+                } else {
+                    Expr::Sum(Box::new(a), Box::new(b))
+                }
+            },
+        }
+    }
+
+    pub fn multiply(a:Expr, b:Expr) -> Expr {
+        if let Some(c) = a.as_constant() {
+            if let Some(scaled) = b.scale(c) {
+                return scaled;
+            }
+        }
+        if let Some(c) = b.as_constant() {
+            if let Some(scaled) = a.scale(c) {
+                return scaled;
+            }
+        }
+        if a.as_constant() == Some(0) || b.as_constant() == Some(0) {
+            return Expr::constant(0);
+        }
+        if a.as_constant() == Some(1) {
+            return b;
+        }
+        if b.as_constant() == Some(1) {
+            return a;
+        }
+        Expr::Product(Box::new(a), Box::new(b))
+    }
+
+    pub fn less_than(a:Expr, b:Expr) -> Expr {
+        match (a.as_constant(), b.as_constant()) {
+            (Some(x), Some(y)) => Expr::constant((x < y) as i64),
+            _ => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn equals(a:Expr, b:Expr) -> Expr {
+        match (a.as_constant(), b.as_constant()) {
+            (Some(x), Some(y)) => Expr::constant((x == y) as i64),
+            _ if a == b => Expr::constant(1),
+            _ => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Expr::Linear(_, terms) => 1 + terms.len(),
+            Expr::Sum(a, b) | Expr::Product(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => 1 + a.size() + b.size(),
+            Expr::Load(address) => 1 + address.size(),
+            Expr::Unknown => 1,
+        }
+    }
+
+    // Evaluates the expression for concrete symbol values, or None when it
+    // contains a Load.
+    pub fn evaluate(&self, values:&Vec<i64>) -> Option<i64> {
+        match self {
+            Expr::Linear(constant, terms) => Some(terms.iter().fold(*constant, |sum, (&s, &c)| sum.wrapping_add(c.wrapping_mul(values[s])))),
+            Expr::Sum(a, b) => Some(a.evaluate(values)?.wrapping_add(b.evaluate(values)?)),
+            Expr::Product(a, b) => Some(a.evaluate(values)?.wrapping_mul(b.evaluate(values)?)),
+            Expr::LessThan(a, b) => Some((a.evaluate(values)? < b.evaluate(values)?) as i64),
+            Expr::Equals(a, b) => Some((a.evaluate(values)? == b.evaluate(values)?) as i64),
+            Expr::Load(_) | Expr::Unknown => None,
+        }
+    }
+
+    pub fn format(&self, names:&Vec<String>) -> String {
+        match self {
+            Expr::Linear(constant, terms) => {
+                let mut parts:Vec<String> = Vec::new();
+                for (&s, &c) in terms {
+                    match c {
+                        1 => parts.push(names[s].clone()),
+                        -1 => parts.push(format!("-{}", names[s])),
+                        _ => parts.push(format!("{}*{}", c, names[s])),
+                    }
+                }
+                if *constant != 0 || parts.is_empty() {
+                    parts.push(constant.to_string());
+                }
+                let text = parts.join(" + ").replace("+ -", "- ");
+                if parts.len() > 1 { format!("({})", text) } else { text }
+            },
+            Expr::Sum(a, b) => format!("({} + {})", a.format(names), b.format(names)),
+            Expr::Product(a, b) => format!("{} * {}", a.format(names), b.format(names)),
+            Expr::LessThan(a, b) => format!("({} < {})", a.format(names), b.format(names)),
+            Expr::Equals(a, b) => format!("({} == {})", a.format(names), b.format(names)),
+            Expr::Load(address) => format!("mem[{}]", address.format(names)),
+            Expr::Unknown => "?".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Source {
+    Input,
+    Memory(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub source: Source,
+    pub low: i64,
+    pub high: i64,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum PathEnd {
+    Finished,
+    Blocked,
+    Killed,
+    StepLimit,
+    BranchLimit,
+    Unsupported(String),
+}
+
+// One execution path: its final memory and outputs, plus the branch
+// conditions (expression, whether it was non-zero) that lead there.
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub end: PathEnd,
+    pub memory: HashMap<usize, Expr>,
+    pub outputs: Vec<Expr>,
+    pub constraints: Vec<(Expr, bool)>,
+}
+
+#[derive(Clone, Debug)]
+struct State {
+    memory: HashMap<usize, Expr>,
+    program_counter: usize,
+    relative_base: i64,
+    inputs: VecDeque<Expr>,
+    outputs: Vec<Expr>,
+    constraints: Vec<(Expr, bool)>,
+    steps: usize,
+}
+
+impl State {
+    fn read(&self, address:usize) -> Expr {
+        self.memory.get(&address).cloned().unwrap_or(Expr::constant(0))
+    }
+
+    fn load(&self, mode:ParameterMode, offset:usize) -> Result<Expr, String> {
+        let word = self.read(self.program_counter + offset);
+        match mode {
+            ParameterMode::Immediate => Ok(word),
+            ParameterMode::Position => match word.as_constant() {
+                Some(address) => Ok(self.read(address as usize)),
+                None => Ok(Expr::Load(Box::new(word))),
+            },
+            ParameterMode::Relative => match word.as_constant() {
+                Some(offset) => Ok(self.read((self.relative_base + offset) as usize)),
+                None => Ok(Expr::Load(Box::new(Expr::add(Expr::constant(self.relative_base), word)))),
+            },
+            ParameterMode::Unknown => Err("unknown parameter mode".to_string()),
+        }
+    }
+
+    fn store(&mut self, mode:ParameterMode, offset:usize, value:Expr) -> Result<(), String> {
+        let word = self.read(self.program_counter + offset);
+        let address = match (mode, word.as_constant()) {
+            (ParameterMode::Relative, Some(offset)) => (self.relative_base + offset) as usize,
+            (ParameterMode::Unknown, _) => return Err("unknown parameter mode".to_string()),
+            (_, Some(address)) => address as usize,
+            (_, None) => return Err(format!("store through symbolic address at {}", self.program_counter)),
+        };
+        if value.size() > MAX_EXPRESSION_SIZE {
+            return Err(format!("expression too large at {}", self.program_counter));
+        }
+        self.memory.insert(address, value);
+        Ok(())
+    }
+}
+
+pub struct Explorer {
+    pub max_paths: usize,
+    pub max_steps: usize,
+    pub max_branches: usize,
+    decoder: Intcode,
+}
+
+impl Explorer {
+    // Loops whose exit depends on a symbol fork on every iteration, so a path
+    // is abandoned once it has taken max_branches symbolic branches.
+    pub fn new(max_paths:usize, max_steps:usize, max_branches:usize) -> Explorer {
+        Explorer { max_paths: max_paths, max_steps: max_steps, max_branches: max_branches, decoder: Intcode::new(Vec::new()) }
+    }
+
+    // Executes the program with the given inputs (symbolic or concrete) and
+    // memory cells replaced by symbols, forking at every branch whose
+    // condition depends on a symbol.
+    pub fn explore(&mut self, program:&Vec<i64>, inputs:&Vec<Expr>, cells:&Vec<(usize, Expr)>) -> Vec<Path> {
+        let mut memory:HashMap<usize, Expr> = program.iter().enumerate().map(|(i, &v)| (i, Expr::constant(v))).collect();
+        for (address, value) in cells {
+            memory.insert(*address, value.clone());
+        }
+        let mut pending:Vec<State> = vec![State {
+            memory: memory,
+            program_counter: 0,
+            relative_base: 0,
+            inputs: inputs.iter().cloned().collect(),
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            steps: 0,
+        }];
+        let mut paths:Vec<Path> = Vec::new();
+
+        while let Some(mut state) = pending.pop() {
+            if paths.len() >= self.max_paths {
+                break;
+            }
+            let end = loop {
+                if state.steps >= self.max_steps {
+                    break PathEnd::StepLimit;
+                }
+                state.steps += 1;
+                match self.step(&mut state) {
+                    Ok(None) => {},
+                    Ok(Some(Fork(taken))) => pending.push(taken),
+                    Err(end) => break end,
+                }
+            };
+            paths.push(Path { end: end, memory: state.memory, outputs: state.outputs, constraints: state.constraints });
+        }
+        paths
+    }
+
+    fn step(&mut self, state:&mut State) -> Result<Option<Fork>, PathEnd> {
+        let pc = state.program_counter;
+        let instruction = match state.read(pc).as_constant() {
+            Some(value) => value,
+            None => return Err(PathEnd::Unsupported(format!("symbolic instruction at {}", pc))),
+        };
+        let opcode = self.decoder.opcode(instruction);
+        let mode = |offset:usize| self.decoder.get_parameter_mode(instruction, offset);
+        let unsupported = |e:String| PathEnd::Unsupported(e);
+
+        match opcode {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+                let a = state.load(mode(1), 1).map_err(unsupported)?;
+                let b = state.load(mode(2), 2).map_err(unsupported)?;
+                let value = match opcode {
+                    Opcode::Add => Expr::add(a, b),
+                    Opcode::Multiply => Expr::multiply(a, b),
+                    Opcode::LessThan => Expr::less_than(a, b),
+                    _ => Expr::equals(a, b),
+                };
+                state.store(mode(3), 3, value).map_err(unsupported)?;
+                state.program_counter += 4;
+            },
+            Opcode::Input => {
+                match state.inputs.pop_front() {
+                    Some(value) => state.store(mode(1), 1, value).map_err(unsupported)?,
+                    None => return Err(PathEnd::Blocked),
+                }
+                state.program_counter += 2;
+            },
+            Opcode::Output => {
+                let value = state.load(mode(1), 1).map_err(unsupported)?;
+                state.outputs.push(value);
+                state.program_counter += 2;
+            },
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = state.load(mode(1), 1).map_err(unsupported)?;
+                let target = match state.load(mode(2), 2).map_err(unsupported)?.as_constant() {
+                    Some(target) => target as usize,
+                    None => return Err(PathEnd::Unsupported(format!("symbolic jump target at {}", pc))),
+                };
+                let jump_if = opcode == Opcode::JumpIfTrue;
+                match condition.as_constant() {
+                    Some(value) => {
+                        state.program_counter = if (value != 0) == jump_if { target } else { pc + 3 };
+                    },
+                    None if state.constraints.len() >= self.max_branches => return Err(PathEnd::BranchLimit),
+                    None => {
+                        let mut taken = state.clone();
+                        taken.constraints.push((condition.clone(), jump_if));
+                        taken.program_counter = target;
+                        state.constraints.push((condition, !jump_if));
+                        state.program_counter = pc + 3;
+                        return Ok(Some(Fork(taken)));
+                    },
+                }
+            },
+            Opcode::AdjustRelativeBase => {
+                match state.load(mode(1), 1).map_err(unsupported)?.as_constant() {
+                    Some(value) => state.relative_base += value,
+                    None => return Err(PathEnd::Unsupported(format!("symbolic relative base at {}", pc))),
+                }
+                state.program_counter += 2;
+            },
+            Opcode::Return => return Err(PathEnd::Finished),
+            Opcode::Unknown => return Err(PathEnd::Killed),
+        }
+        Ok(None)
+    }
+}
+
+struct Fork(State);
+
+fn satisfies(constraints:&Vec<(Expr, bool)>, values:&Vec<i64>) -> bool {
+    constraints.iter().all(|(condition, holds)| match condition.evaluate(values) {
+        Some(value) => (value != 0) == *holds,
+        None => true,
+    })
+}
+
+// Searches symbol values within their domains for which `goal == target`
+// under the path constraints, handing every candidate to `check` until it
+// accepts one. When the goal is linear in some symbol, that symbol is solved
+// for directly instead of enumerated.
+pub fn solve(goal:&Expr, target:i64, constraints:&Vec<(Expr, bool)>, symbols:&Vec<Symbol>, check:&mut dyn FnMut(&Vec<i64>) -> bool) -> Option<Vec<i64>> {
+    let solved = match goal {
+        Expr::Linear(_, terms) => terms.iter()
+                                       .max_by_key(|&(&s, _)| symbols[s].high as i128 - symbols[s].low as i128)
+                                       .map(|(&s, &c)| (s, c)),
+        _ => None,
+    };
+    let enumerated:Vec<usize> = (0..symbols.len()).filter(|&s| solved.map(|(v, _)| v) != Some(s)).collect();
+    let mut values:Vec<i64> = symbols.iter().map(|s| s.low).collect();
+
+    loop {
+        if let Some((symbol, coefficient)) = solved {
+            values[symbol] = 0;
+            // Only the solution without wraparound is tried; check confirms
+            // it on the VM.
+            let difference = target.wrapping_sub(goal.evaluate(&values).unwrap());
+            if difference.wrapping_rem(coefficient) == 0 {
+                let value = difference.wrapping_div(coefficient);
+                if value >= symbols[symbol].low && value <= symbols[symbol].high {
+                    values[symbol] = value;
+                    if satisfies(constraints, &values) && check(&values) {
+                        return Some(values);
+                    }
+                }
+            }
+        } else if goal.evaluate(&values).map_or(true, |v| v == target) && satisfies(constraints, &values) && check(&values) {
+            return Some(values);
+        }
+
+        // Advance the enumerated symbols like an odometer.
+        let mut i = 0;
+        loop {
+            if i == enumerated.len() {
+                return None;
+            }
+            let s = enumerated[i];
+            if values[s] < symbols[s].high {
+                values[s] += 1;
+                break;
+            }
+            values[s] = symbols[s].low;
+            i += 1;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Goal {
+    Output(usize, i64),
+    Memory(usize, i64),
+}
+
+// Runs the program concretely with the symbol values filled in and tells
+// whether it meets the goal.
+pub fn verify(program:&Vec<i64>, inputs:&Vec<Option<i64>>, symbols:&Vec<Symbol>, values:&Vec<i64>, goal:&Goal, max_ticks:usize) -> bool {
+    let mut computer = Intcode::new(program.clone());
+    let mut next_symbol = symbols.iter().enumerate().filter(|(_, s)| match s.source { Source::Input => true, _ => false }).map(|(i, _)| i);
+    for input in inputs {
+        match input {
+            Some(value) => computer.input(*value),
+            None => computer.input(values[next_symbol.next().unwrap()]),
+        }
+    }
+    for (i, symbol) in symbols.iter().enumerate() {
+        if let Source::Memory(address) = symbol.source {
+            computer.write(address, values[i]);
+        }
+    }
+    let mut ticks = 0;
+    while computer.status == Status::Running && ticks < max_ticks {
+        computer.tick();
+        ticks += 1;
+    }
+    match goal {
+        Goal::Output(index, value) => computer.outputs.get(*index) == Some(value),
+        Goal::Memory(address, value) => computer.status == Status::Finished && computer.read(*address) == *value,
+    }
+}
+
+// A path that may reach the goal, with its goal expression formatted.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub path: usize,
+    pub end: PathEnd,
+    pub constraints: usize,
+    pub expression: String,
+}
+
+// What a search explored and tried, and the first solution the concrete run
+// confirmed, if any.
+#[derive(Clone, Debug)]
+pub struct Search {
+    pub paths: usize,
+    pub candidates: Vec<Candidate>,
+    pub solution: Option<Vec<i64>>,
+}
+
+// Explores the program symbolically and solves every path that reaches the
+// goal, in order, until the concrete run confirms a solution.
+pub fn search(program:&Vec<i64>, inputs:&Vec<Option<i64>>, symbols:&Vec<Symbol>, goal:&Goal, max_ticks:usize) -> Search {
+    let names:Vec<String> = symbols.iter().map(|s| s.name.clone()).collect();
+    let mut input_symbols = symbols.iter().enumerate().filter(|(_, s)| match s.source { Source::Input => true, _ => false }).map(|(i, _)| i);
+    let symbolic_inputs:Vec<Expr> = inputs.iter().map(|input| match input {
+        Some(value) => Expr::constant(*value),
+        None => Expr::symbol(input_symbols.next().expect("More symbolic inputs than input symbols")),
+    }).collect();
+    let cells:Vec<(usize, Expr)> = symbols.iter().enumerate().filter_map(|(i, s)| match s.source {
+        Source::Memory(address) => Some((address, Expr::symbol(i))),
+        Source::Input => None,
+    }).collect();
+
+    let mut explorer = Explorer::new(10_000, max_ticks, 64);
+    let paths = explorer.explore(program, &symbolic_inputs, &cells);
+    let mut result = Search { paths: paths.len(), candidates: Vec::new(), solution: None };
+
+    for (i, path) in paths.iter().enumerate() {
+        let stopped = match path.end {
+            PathEnd::Unsupported(_) | PathEnd::StepLimit | PathEnd::BranchLimit => true,
+            _ => false,
+        };
+        // A path exploration gave up on can still reach the goal later, so its
+        // constraints are kept and the candidates are left to the concrete run.
+        let (expression, target) = match goal {
+            Goal::Output(index, value) => match path.outputs.get(*index) {
+                Some(expression) => (expression.clone(), *value),
+                None if stopped => (Expr::Unknown, *value),
+                None => continue,
+            },
+            Goal::Memory(_, value) if stopped => (Expr::Unknown, *value),
+            Goal::Memory(address, value) if path.end == PathEnd::Finished => {
+                (path.memory.get(address).cloned().unwrap_or(Expr::constant(0)), *value)
+            },
+            Goal::Memory(_, _) => continue,
+        };
+        result.candidates.push(Candidate {
+            path: i,
+            end: path.end.clone(),
+            constraints: path.constraints.len(),
+            expression: expression.format(&names),
+        });
+        result.solution = solve(&expression, target, &path.constraints, symbols, &mut |values| verify(program, inputs, symbols, values, goal, max_ticks));
+        if result.solution.is_some() {
+            break;
+        }
+    }
+    result
+}
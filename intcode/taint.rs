@@ -0,0 +1,61 @@
+use std::collections::BTreeSet;
+
+use super::vm::{Intcode, Status, Taint};
+
+fn describe(taint:&Taint, tags:&BTreeSet<usize>) -> String {
+    if tags.is_empty() {
+        "constant".to_string()
+    } else {
+        tags.iter().map(|&t| taint.sources[t].clone()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+// Runs the program with taint tracking. Each cell is an address, optionally
+// with a value to store there first (`1=12`), that becomes a named source.
+pub fn run(program:&Vec<i64>, inputs:&Vec<i64>, cells:&Vec<(usize, Option<i64>)>, max_ticks:usize) -> Intcode {
+    let mut computer = Intcode::new(program.clone());
+    computer.set_taint(true);
+    for &(address, value) in cells {
+        if let Some(value) = value {
+            computer.write(address, value);
+        }
+        computer.taint_memory(address, &format!("memory {}", address));
+    }
+    computer.append_input(inputs);
+    let mut ticks = 0;
+    while computer.status == Status::Running && ticks < max_ticks {
+        computer.tick();
+        ticks += 1;
+    }
+    computer
+}
+
+pub fn report(computer:&Intcode) {
+    let taint = computer.taint().unwrap();
+    println!("Run ended with status {:?}", computer.status);
+
+    println!();
+    println!("Outputs:");
+    for (i, tags) in taint.outputs.iter().enumerate() {
+        let value = computer.outputs.get(i).map(|v| v.to_string()).unwrap_or("?".to_string());
+        println!("  #{:<4} {:>16}  <- {}", i, value, describe(taint, tags));
+    }
+
+    println!();
+    println!("Branches decided by sources:");
+    for (address, branch) in &taint.branches {
+        println!("  {:>6}  taken {:>6}, not taken {:>6}  <- {}", address, branch.taken, branch.not_taken, describe(taint, &branch.sources));
+    }
+
+    let mut cells:Vec<(&usize, &BTreeSet<usize>)> = taint.memory.iter().collect();
+    cells.sort();
+    println!();
+    println!("Tainted memory at the end of the run:");
+    for (address, tags) in cells.iter().take(50) {
+        let value = computer.memory.get(address).cloned().unwrap_or(0);
+        println!("  {:>6} {:>16}  <- {}", address, value, describe(taint, tags));
+    }
+    if cells.len() > 50 {
+        println!("  ... {} more", cells.len() - 50);
+    }
+}
@@ -0,0 +1,175 @@
+use super::disassembler;
+use super::vm::{Instruction, Opcode, Parameter};
+
+fn load(parameter:Parameter) -> String {
+    match parameter {
+        Parameter::Position(address) => format!("self.vm.read({})", address),
+        Parameter::Immediate(value) => format!("{}", value),
+        Parameter::Relative(offset) => format!("self.vm.read(self.vm.relative_base.wrapping_add({}) as usize)", offset),
+        Parameter::Unknown => unreachable!(),
+    }
+}
+
+fn address(parameter:Parameter) -> String {
+    match parameter {
+        Parameter::Position(address) => format!("{}", address),
+        Parameter::Immediate(value) => format!("{}", value as usize),
+        Parameter::Relative(offset) => format!("self.vm.relative_base.wrapping_add({}) as usize", offset),
+        Parameter::Unknown => unreachable!(),
+    }
+}
+
+fn translate_instruction(pc:usize, instruction:&Instruction) -> Option<String> {
+    let p = instruction.parameters;
+    let next = pc + instruction.length();
+    let body = match instruction.opcode {
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+            if p.iter().any(|&p| p == Parameter::Unknown) {
+                return None;
+            }
+            let value = match instruction.opcode {
+                Opcode::Add => "a.wrapping_add(b)",
+                Opcode::Multiply => "a.wrapping_mul(b)",
+                Opcode::LessThan => "(a < b) as i64",
+                _ => "(a == b) as i64",
+            };
+            format!("let a:i64 = {};\n                let b:i64 = {};\n                let address = {};\n                self.store(address, {});\n                self.vm.program_counter = {};",
+                    load(p[0]), load(p[1]), address(p[2]), value, next)
+        },
+        Opcode::Input => {
+            if p[0] == Parameter::Unknown {
+                return None;
+            }
+            format!("if let Some(input) = self.vm.inputs.pop_front() {{\n                    let address = {};\n                    self.store(address, input);\n                    self.vm.program_counter = {};\n                }} else {{\n                    self.vm.status = Status::Blocked;\n                }}",
+                    address(p[0]), next)
+        },
+        Opcode::Output => {
+            if p[0] == Parameter::Unknown {
+                return None;
+            }
+            format!("let output = {};\n                self.vm.outputs.push_back(output);\n                self.vm.program_counter = {};", load(p[0]), next)
+        },
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            if p[0] == Parameter::Unknown || p[1] == Parameter::Unknown {
+                return None;
+            }
+            let comparison = if instruction.opcode == Opcode::JumpIfTrue { "!=" } else { "==" };
+            format!("if {} {} 0 {{\n                    self.vm.program_counter = ({}) as usize;\n                }} else {{\n                    self.vm.program_counter = {};\n                }}",
+                    load(p[0]), comparison, load(p[1]), next)
+        },
+        Opcode::AdjustRelativeBase => {
+            if p[0] == Parameter::Unknown {
+                return None;
+            }
+            format!("self.vm.relative_base = self.vm.relative_base.wrapping_add({});\n                self.vm.program_counter = {};", load(p[0]), next)
+        },
+        Opcode::Return => "self.vm.status = Status::Finished;".to_string(),
+        Opcode::Unknown => return None,
+    };
+    Some(body)
+}
+
+// Emits a Rust module that runs the program with one match arm per reachable
+// instruction. The module expects the VM to be reachable as `super::vm`.
+pub fn translate(program:&Vec<i64>, source:&str) -> String {
+    let instructions = disassembler::reachable(program);
+    let mut out = String::new();
+
+    out.push_str(&format!("// Generated by `intcode translate` from {}. Do not edit.\n", source));
+    out.push_str("#![allow(dead_code, unused_parens, unreachable_patterns, clippy::all)]\n\n");
+    out.push_str("use std::ops::Deref;\nuse std::ops::DerefMut;\n\nuse super::vm::{Intcode, Status};\n\n");
+    out.push_str(&format!("pub const IMAGE:[i64; {}] = [", program.len()));
+    for (i, value) in program.iter().enumerate() {
+        if i % 16 == 0 {
+            out.push_str("\n    ");
+        } else {
+            out.push(' ');
+        }
+        out.push_str(&format!("{},", value));
+    }
+    out.push_str("\n];\n\n");
+
+    out.push_str(TRANSLATED_HEADER);
+    for (pc, instruction) in &instructions {
+        if let Some(body) = translate_instruction(*pc, instruction) {
+            out.push_str(&format!("            {} if !self.modified({}, {}) => {{\n                {}\n            }},\n",
+                                  pc, pc, instruction.length(), body));
+        }
+    }
+    out.push_str(TRANSLATED_FOOTER);
+    out
+}
+
+const TRANSLATED_HEADER:&str = "pub struct Translated {
+    pub vm: Intcode,
+    modified: Vec<bool>,
+}
+
+impl Deref for Translated {
+    type Target = Intcode;
+
+    fn deref(&self) -> &Intcode {
+        &self.vm
+    }
+}
+
+impl DerefMut for Translated {
+    fn deref_mut(&mut self) -> &mut Intcode {
+        &mut self.vm
+    }
+}
+
+impl Translated {
+    pub fn new() -> Translated {
+        Translated {
+            vm: Intcode::new(IMAGE.to_vec()),
+            modified: vec![false; IMAGE.len()],
+        }
+    }
+
+    pub fn execute(&mut self) {
+        while self.vm.status == Status::Running {
+            self.step();
+        }
+    }
+
+    pub fn r#continue(&mut self) {
+        if self.vm.blocked() && self.vm.inputs.len() > 0 {
+            self.vm.status = Status::Running;
+        }
+        self.execute();
+    }
+
+    // Instructions whose words were overwritten since translation are left
+    // to the interpreter.
+    fn modified(&self, start:usize, length:usize) -> bool {
+        self.modified[start..start + length].iter().any(|&m| m)
+    }
+
+    fn store(&mut self, address:usize, value:i64) {
+        if address < self.modified.len() {
+            self.modified[address] = true;
+        }
+        self.vm.write(address, value);
+    }
+
+    fn step(&mut self) {
+        match self.vm.program_counter {
+";
+
+const TRANSLATED_FOOTER:&str = "            _ => {
+                // Untranslated or modified code runs in the interpreter, but
+                // its writes still have to retire the arms they overwrite.
+                let instruction = self.vm.decode(self.vm.program_counter);
+                let store = instruction.store_address(self.vm.relative_base);
+                self.vm.tick();
+                if let Some(address) = store {
+                    if address < self.modified.len() {
+                        self.modified[address] = true;
+                    }
+                }
+            },
+        }
+    }
+}
+";
@@ -0,0 +1,779 @@
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::collections::HashMap;
+use std::collections::BTreeSet;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+// Instructions at higher addresses are decoded every time they run, so a
+// jump far past the program does not grow the decode cache to match.
+const MAX_CACHED_ADDRESS:usize = 1 << 20;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Opcode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Return,
+    Unknown,
+}
+
+impl Opcode {
+    pub fn length(&self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 4,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 3,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 2,
+            Opcode::Return | Opcode::Unknown => 1,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Status {
+    Running,
+    Finished,
+    Blocked,
+    Killed,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+    Relative,
+    Unknown,
+}
+
+// A decoded parameter. Position parameters carry the address they point to,
+// immediate parameters their value and relative parameters the offset that
+// is added to the relative base when the instruction is executed.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Parameter {
+    Position(usize),
+    Immediate(i64),
+    Relative(i64),
+    Unknown,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub modes: [ParameterMode; 3],
+    pub parameters: [Parameter; 3],
+}
+
+impl Instruction {
+    pub fn length(&self) -> usize {
+        self.opcode.length()
+    }
+
+    // The address this instruction writes to, given the current relative base.
+    pub fn store_address(&self, relative_base:i64) -> Option<usize> {
+        let parameter = match self.opcode {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => self.parameters[2],
+            Opcode::Input => self.parameters[0],
+            _ => return None,
+        };
+        match parameter {
+            Parameter::Position(address) => Some(address),
+            Parameter::Immediate(address) => Some(address as usize),
+            Parameter::Relative(offset) => Some(relative_base.wrapping_add(offset) as usize),
+            Parameter::Unknown => None,
+        }
+    }
+}
+
+// Why a program was killed and the address of the instruction that did it.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum FaultKind {
+    UnknownOpcode(i64),
+    UnknownParameterMode(i64),
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Fault {
+    pub program_counter: usize,
+    pub kind: FaultKind,
+}
+
+// The machine state without instrumentation or decode cache, for saving a
+// run and resuming it later.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Snapshot {
+    pub status: Status,
+    pub memory: HashMap<usize, i64>,
+    pub program_counter: usize,
+    pub relative_base: i64,
+    pub inputs: VecDeque<i64>,
+    pub outputs: VecDeque<i64>,
+    pub fault: Option<Fault>,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct SelfModification {
+    pub program_counter: usize,
+    pub address: usize,
+    pub value: i64,
+}
+
+// Records how a run touched memory: the words of every executed instruction,
+// the addresses read and written as data, and every write that landed on a
+// word that had already been executed as part of an instruction.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTrace {
+    pub instructions: BTreeSet<usize>,
+    pub executed: BTreeSet<usize>,
+    pub read: BTreeSet<usize>,
+    pub written: BTreeSet<usize>,
+    pub self_modifications: Vec<SelfModification>,
+}
+
+// Execution counts per instruction address. Coverage from several runs of
+// the same program can be merged into one.
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    pub hits: HashMap<usize, u64>,
+    pub runs: u64,
+}
+
+impl Coverage {
+    pub fn merge(&mut self, other:&Coverage) {
+        for (&address, &count) in &other.hits {
+            *self.hits.entry(address).or_insert(0) += count;
+        }
+        self.runs += other.runs;
+    }
+
+    pub fn count(&self, address:usize) -> u64 {
+        *self.hits.get(&address).unwrap_or(&0)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BranchTaint {
+    pub taken: u64,
+    pub not_taken: u64,
+    pub sources: BTreeSet<usize>,
+}
+
+// Dynamic taint state. Every value read by an Input instruction and every
+// memory cell marked with `taint_memory` is a source; the tags flow through
+// Add, Multiply, LessThan and Equals into the cells they store to. Only data
+// flow is followed: a tainted relative base or jump does not taint the
+// values it selects.
+#[derive(Clone, Debug, Default)]
+pub struct Taint {
+    pub sources: Vec<String>,
+    pub memory: HashMap<usize, BTreeSet<usize>>,
+    pub outputs: Vec<BTreeSet<usize>>,
+    pub branches: BTreeMap<usize, BranchTaint>,
+    inputs_consumed: usize,
+}
+
+// Everything a run read and wrote through I/O: each input with the number of
+// outputs produced before it was consumed, and every output in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    pub inputs: Vec<(usize, i64)>,
+    pub outputs: Vec<i64>,
+}
+
+// Callbacks for what happens while a program runs. Every method does nothing
+// by default, so an observer only implements the events it cares about.
+pub trait Observer {
+    fn instruction(&mut self, _program_counter:usize, _instruction:&Instruction) {}
+    fn memory_write(&mut self, _address:usize, _value:i64) {}
+    fn input(&mut self, _value:i64) {}
+    fn output(&mut self, _value:i64) {}
+    fn status(&mut self, _status:&Status) {}
+}
+
+// Observers are shared with clones of the machine, so a droid copied to try
+// something out reports to the same places as the original.
+#[derive(Clone, Default)]
+struct Observers(Vec<Arc<Mutex<dyn Observer + Send>>>);
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
+
+impl Observers {
+    // Kept out of line so that running without observers costs one branch.
+    #[cold]
+    #[inline(never)]
+    fn notify(&self, event:&dyn Fn(&mut dyn Observer)) {
+        for observer in &self.0 {
+            event(&mut *observer.lock().unwrap());
+        }
+    }
+}
+
+// Prints an ASCII program's conversation as it happens: the inputs it
+// consumes and the outputs it produces. Values outside ASCII are left to the
+// caller.
+pub struct AsciiEcho;
+
+impl Observer for AsciiEcho {
+    fn input(&mut self, value:i64) {
+        self.output(value);
+    }
+
+    fn output(&mut self, value:i64) {
+        if value >= 0 && value < 128 {
+            print!("{}", value as u8 as char);
+            if value == 10 {
+                std::io::stdout().flush().ok();
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Intcode {
+    pub status: Status,
+    pub memory: HashMap<usize, i64>,
+    pub program_counter: usize,
+    pub relative_base: i64,
+    pub inputs: VecDeque<i64>,
+    pub outputs: VecDeque<i64>,
+    instruction: Instruction,
+    fault: Option<Fault>,
+    decode_cache: Option<Vec<Option<Instruction>>>,
+    memory_trace: Option<MemoryTrace>,
+    coverage: Option<Coverage>,
+    taint: Option<Taint>,
+    recording: Option<Recording>,
+    observers: Observers,
+}
+
+impl Intcode {
+    pub fn new(program:Vec<i64>) -> Intcode {
+        let mut m:HashMap<usize, i64> = HashMap::new();
+        for (i, &v) in program.iter().enumerate() {
+            m.insert(i, v);
+        }
+        Intcode {
+            status: Status::Running,
+            memory: m,
+            program_counter: 0,
+            relative_base: 0,
+            inputs: VecDeque::new(),
+            outputs: VecDeque::new(),
+            instruction: Instruction {
+                opcode: Opcode::Unknown,
+                modes: [ParameterMode::Unknown; 3],
+                parameters: [Parameter::Unknown; 3],
+            },
+            fault: None,
+            decode_cache: Some(vec![None; program.len()]),
+            memory_trace: None,
+            coverage: None,
+            taint: None,
+            recording: None,
+            observers: Observers::default(),
+        }
+    }
+
+    // Registers an observer and returns it, so the caller can read what it
+    // collected. Without observers the only cost is an emptiness check per
+    // event.
+    pub fn add_observer<O:Observer + Send + 'static>(&mut self, observer:O) -> Arc<Mutex<O>> {
+        let observer = Arc::new(Mutex::new(observer));
+        self.observers.0.push(observer.clone());
+        observer
+    }
+
+    pub fn clear_observers(&mut self) {
+        self.observers.0.clear();
+    }
+
+    fn set_status(&mut self, status:Status) {
+        if self.status != status {
+            self.status = status;
+            if !self.observers.0.is_empty() {
+                let status = self.status.clone();
+                self.observers.notify(&|o| o.status(&status));
+            }
+        }
+    }
+
+    pub fn set_decode_cache(&mut self, enabled:bool) {
+        if !enabled {
+            self.decode_cache = None;
+        } else if self.decode_cache.is_none() {
+            self.decode_cache = Some(Vec::new());
+        }
+    }
+
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            status: self.status.clone(),
+            memory: self.memory.clone(),
+            program_counter: self.program_counter,
+            relative_base: self.relative_base,
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+            fault: self.fault,
+        }
+    }
+
+    // Puts the machine back into a saved state. Cached instructions may have
+    // been decoded from memory that differs from the snapshot, so the cache
+    // starts over.
+    pub fn restore(&mut self, snapshot:&Snapshot) {
+        self.status = snapshot.status.clone();
+        self.memory = snapshot.memory.clone();
+        self.program_counter = snapshot.program_counter;
+        self.relative_base = snapshot.relative_base;
+        self.inputs = snapshot.inputs.clone();
+        self.outputs = snapshot.outputs.clone();
+        self.fault = snapshot.fault;
+        if let Some(cache) = self.decode_cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    pub fn set_memory_trace(&mut self, enabled:bool) {
+        if !enabled {
+            self.memory_trace = None;
+        } else if self.memory_trace.is_none() {
+            self.memory_trace = Some(MemoryTrace::default());
+        }
+    }
+
+    pub fn memory_trace(&self) -> Option<&MemoryTrace> {
+        self.memory_trace.as_ref()
+    }
+
+    pub fn set_coverage(&mut self, enabled:bool) {
+        if !enabled {
+            self.coverage = None;
+        } else if self.coverage.is_none() {
+            self.coverage = Some(Coverage { hits: HashMap::new(), runs: 1 });
+        }
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn set_taint(&mut self, enabled:bool) {
+        if !enabled {
+            self.taint = None;
+        } else if self.taint.is_none() {
+            self.taint = Some(Taint::default());
+        }
+    }
+
+    pub fn taint(&self) -> Option<&Taint> {
+        self.taint.as_ref()
+    }
+
+    pub fn set_recording(&mut self, enabled:bool) {
+        if !enabled {
+            self.recording = None;
+        } else if self.recording.is_none() {
+            self.recording = Some(Recording::default());
+        }
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    // Marks a memory cell as a taint source of its own, e.g. day2's noun and
+    // verb. Taint tracking has to be enabled first.
+    pub fn taint_memory(&mut self, address:usize, name:&str) {
+        let taint = self.taint.as_mut().expect("Taint tracking is not enabled");
+        taint.memory.entry(address).or_insert_with(BTreeSet::new).insert(taint.sources.len());
+        taint.sources.push(name.to_string());
+    }
+
+    pub fn input(&mut self, value:i64) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn append_input(&mut self, values:&Vec<i64>) {
+        self.inputs.append(&mut VecDeque::from(values.to_vec()));
+    }
+
+    pub fn output_to_string(&self) -> String {
+        self.outputs.iter().map(|&c| c as u8 as char).collect::<String>()
+    }
+
+    pub fn get_output_string(&mut self) -> String {
+        let output = self.output_to_string();
+        self.outputs.clear();
+        output
+    }
+
+    pub fn execute(&mut self) {
+        while self.status == Status::Running {
+            self.tick();
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.instruction = self.fetch();
+        let program_counter = self.program_counter;
+        if let Some(kind) = self.check_instruction() {
+            self.fault = Some(Fault { program_counter: program_counter, kind: kind });
+            self.set_status(Status::Killed);
+            return;
+        }
+        if let Some(trace) = self.memory_trace.as_mut() {
+            trace.instructions.insert(program_counter);
+            for offset in 0..self.instruction.length() {
+                trace.executed.insert(program_counter.wrapping_add(offset));
+            }
+        }
+        if self.taint.is_some() {
+            self.propagate_taint();
+        }
+        match self.instruction.opcode {
+            Opcode::Add => {
+                let a = self.get_parameter(1);
+                let b = self.get_parameter(2);
+                self.store_position(3, a.wrapping_add(b));
+                self.program_counter = self.program_counter.wrapping_add(4);
+            },
+            Opcode::Multiply => {
+                let a = self.get_parameter(1);
+                let b = self.get_parameter(2);
+                self.store_position(3, a.wrapping_mul(b));
+                self.program_counter = self.program_counter.wrapping_add(4);
+            },
+            Opcode::Input => {
+                if let Some(input) = self.inputs.pop_front() {
+                    if let Some(recording) = self.recording.as_mut() {
+                        recording.inputs.push((recording.outputs.len(), input));
+                    }
+                    if !self.observers.0.is_empty() {
+                        self.observers.notify(&|o| o.input(input));
+                    }
+                    self.store_position(1, input);
+                    self.program_counter = self.program_counter.wrapping_add(2);
+                } else {
+                    self.set_status(Status::Blocked);
+                }
+            },
+            Opcode::Output => {
+                let output = self.get_parameter(1);
+                if let Some(recording) = self.recording.as_mut() {
+                    recording.outputs.push(output);
+                }
+                if !self.observers.0.is_empty() {
+                    self.observers.notify(&|o| o.output(output));
+                }
+                self.outputs.push_back(output);
+                self.program_counter = self.program_counter.wrapping_add(2);
+            },
+            Opcode::JumpIfTrue => {
+                let condition = self.get_parameter(1);
+                if condition != 0 {
+                    let jump = self.get_parameter(2) as usize;
+                    self.program_counter = jump;
+                } else {
+                    self.program_counter = self.program_counter.wrapping_add(3);
+                }
+            },
+            Opcode::JumpIfFalse => {
+                let condition = self.get_parameter(1);
+                if condition == 0 {
+                    let jump = self.get_parameter(2) as usize;
+                    self.program_counter = jump;
+                } else {
+                    self.program_counter = self.program_counter.wrapping_add(3);
+                }
+            },
+            Opcode::LessThan => {
+                let a = self.get_parameter(1);
+                let b = self.get_parameter(2);
+                if a < b {
+                    self.store_position(3, 1);
+                } else {
+                    self.store_position(3, 0);
+                }
+                self.program_counter = self.program_counter.wrapping_add(4);
+            },
+            Opcode::Equals => {
+                let a = self.get_parameter(1);
+                let b = self.get_parameter(2);
+                if a == b {
+                    self.store_position(3, 1);
+                } else {
+                    self.store_position(3, 0);
+                }
+                self.program_counter = self.program_counter.wrapping_add(4);
+            },
+            Opcode::AdjustRelativeBase => {
+                let a = self.get_parameter(1);
+                self.relative_base = self.relative_base.wrapping_add(a);
+                self.program_counter = self.program_counter.wrapping_add(2);
+            },
+            Opcode::Return => {
+                self.set_status(Status::Finished);
+            },
+            Opcode::Unknown => {},
+        }
+        if self.status != Status::Blocked {
+            if let Some(coverage) = self.coverage.as_mut() {
+                *coverage.hits.entry(program_counter).or_insert(0) += 1;
+            }
+            if !self.observers.0.is_empty() {
+                self.observers.notify(&|o| o.instruction(program_counter, &self.instruction));
+            }
+        }
+    }
+
+    fn parameter_taint(&self, offset:usize) -> BTreeSet<usize> {
+        let address = match self.instruction.parameters[offset - 1] {
+            Parameter::Position(address) => address,
+            Parameter::Relative(v) => self.relative_base.wrapping_add(v) as usize,
+            _ => return BTreeSet::new(),
+        };
+        match self.taint.as_ref().unwrap().memory.get(&address) {
+            Some(tags) => tags.clone(),
+            None => BTreeSet::new(),
+        }
+    }
+
+    fn parameter_value(&mut self, offset:usize) -> i64 {
+        match self.instruction.parameters[offset - 1] {
+            Parameter::Position(address) => self.read(address),
+            Parameter::Immediate(value) => value,
+            Parameter::Relative(v) => self.read(self.relative_base.wrapping_add(v) as usize),
+            Parameter::Unknown => 0,
+        }
+    }
+
+    // Updates the taint state for the instruction about to be executed,
+    // before it changes any memory.
+    fn propagate_taint(&mut self) {
+        let store = self.instruction.store_address(self.relative_base);
+        match (self.instruction.opcode, store) {
+            (Opcode::Add, Some(address)) | (Opcode::Multiply, Some(address)) |
+            (Opcode::LessThan, Some(address)) | (Opcode::Equals, Some(address)) => {
+                let mut tags = self.parameter_taint(1);
+                tags.extend(self.parameter_taint(2));
+                let taint = self.taint.as_mut().unwrap();
+                if tags.is_empty() {
+                    taint.memory.remove(&address);
+                } else {
+                    taint.memory.insert(address, tags);
+                }
+            },
+            (Opcode::Input, Some(address)) if self.inputs.len() > 0 => {
+                let taint = self.taint.as_mut().unwrap();
+                let mut tags:BTreeSet<usize> = BTreeSet::new();
+                tags.insert(taint.sources.len());
+                taint.sources.push(format!("input {}", taint.inputs_consumed));
+                taint.inputs_consumed += 1;
+                taint.memory.insert(address, tags);
+            },
+            (Opcode::Output, _) => {
+                let tags = self.parameter_taint(1);
+                self.taint.as_mut().unwrap().outputs.push(tags);
+            },
+            (Opcode::JumpIfTrue, _) | (Opcode::JumpIfFalse, _) => {
+                let tags = self.parameter_taint(1);
+                if !tags.is_empty() {
+                    let condition = self.parameter_value(1);
+                    let taken = (condition != 0) == (self.instruction.opcode == Opcode::JumpIfTrue);
+                    let branch = self.taint.as_mut().unwrap().branches.entry(self.program_counter).or_insert_with(BranchTaint::default);
+                    if taken {
+                        branch.taken += 1;
+                    } else {
+                        branch.not_taken += 1;
+                    }
+                    branch.sources.extend(tags);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Returns the decoded instruction at the program counter, decoding it
+    // only the first time the address is executed when the cache is enabled.
+    fn fetch(&mut self) -> Instruction {
+        let pc = self.program_counter;
+        if let Some(Some(instruction)) = self.decode_cache.as_ref().and_then(|cache| cache.get(pc)) {
+            return *instruction;
+        }
+        let instruction = self.decode(pc);
+        if let Some(cache) = self.decode_cache.as_mut().filter(|_| pc < MAX_CACHED_ADDRESS) {
+            if cache.len() <= pc {
+                cache.resize(pc + 1, None);
+            }
+            cache[pc] = Some(instruction);
+        }
+        instruction
+    }
+
+    pub fn decode(&mut self, address:usize) -> Instruction {
+        let value = self.read(address);
+        let opcode = self.opcode(value);
+        let mut modes = [ParameterMode::Unknown; 3];
+        let mut parameters = [Parameter::Unknown; 3];
+        for offset in 1..opcode.length() {
+            let mode = self.get_parameter_mode(value, offset);
+            let raw = self.read(address.wrapping_add(offset));
+            modes[offset - 1] = mode;
+            parameters[offset - 1] = match mode {
+                ParameterMode::Position => Parameter::Position(raw as usize),
+                ParameterMode::Immediate => Parameter::Immediate(raw),
+                ParameterMode::Relative => Parameter::Relative(raw),
+                ParameterMode::Unknown => Parameter::Unknown,
+            };
+        }
+        Instruction { opcode: opcode, modes: modes, parameters: parameters }
+    }
+
+    // Returns why the instruction just fetched cannot be executed, if it can't.
+    fn check_instruction(&self) -> Option<FaultKind> {
+        let value = self.memory.get(&self.program_counter).cloned().unwrap_or(0);
+        if self.instruction.opcode == Opcode::Unknown {
+            return Some(FaultKind::UnknownOpcode(value));
+        }
+        if self.instruction.modes[..self.instruction.length() - 1].contains(&ParameterMode::Unknown) {
+            return Some(FaultKind::UnknownParameterMode(value));
+        }
+        None
+    }
+
+    pub fn opcode(&self, instruction:i64) -> Opcode {
+        match instruction % 100 {
+            1 => Opcode::Add,
+            2 => Opcode::Multiply,
+            3 => Opcode::Input,
+            4 => Opcode::Output,
+            5 => Opcode::JumpIfTrue,
+            6 => Opcode::JumpIfFalse,
+            7 => Opcode::LessThan,
+            8 => Opcode::Equals,
+            9 => Opcode::AdjustRelativeBase,
+            99 => Opcode::Return,
+            _ => Opcode::Unknown,
+        }
+    }
+
+    fn get_parameter(&mut self, offset:usize) -> i64 {
+        match self.instruction.parameters[offset - 1] {
+            Parameter::Position(address) => self.load(address),
+            Parameter::Immediate(value) => value,
+            Parameter::Relative(v) => self.load(self.relative_base.wrapping_add(v) as usize),
+            Parameter::Unknown => 0,
+        }
+    }
+
+    fn store_position(&mut self, offset:usize, value:i64) {
+        let store_index:usize;
+        match self.instruction.parameters[offset - 1] {
+            Parameter::Position(address) => store_index = address,
+            Parameter::Immediate(address) => store_index = address as usize,
+            Parameter::Relative(v) => store_index = self.relative_base.wrapping_add(v) as usize,
+            Parameter::Unknown => return,
+        }
+
+        self.write(store_index, value);
+    }
+
+    // Reads a data operand, as opposed to instruction words which go through
+    // `read` directly.
+    fn load(&mut self, address:usize) -> i64 {
+        if let Some(trace) = self.memory_trace.as_mut() {
+            trace.read.insert(address);
+        }
+        self.read(address)
+    }
+
+    pub fn write(&mut self, address:usize, value:i64) {
+        if let Some(trace) = self.memory_trace.as_mut() {
+            trace.written.insert(address);
+            if trace.executed.contains(&address) {
+                trace.self_modifications.push(SelfModification {
+                    program_counter: self.program_counter,
+                    address: address,
+                    value: value,
+                });
+            }
+        }
+        self.invalidate(address);
+        if !self.observers.0.is_empty() {
+            self.observers.notify(&|o| o.memory_write(address, value));
+        }
+        *self.memory.entry(address).or_insert(0) = value
+    }
+
+    // Drops every cached instruction that the written address is part of, so
+    // self-modifying programs see their new code on the next fetch.
+    fn invalidate(&mut self, address:usize) {
+        if let Some(cache) = self.decode_cache.as_mut() {
+            for start in address.saturating_sub(3)..=address {
+                if let Some(Some(instruction)) = cache.get(start) {
+                    if start + instruction.length() > address {
+                        cache[start] = None;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn read(&mut self, address:usize) -> i64 {
+        self.memory.get(&address).cloned().unwrap_or(0)
+    }
+
+    pub fn get_parameter_mode(&self, instruction:i64, offset:usize) -> ParameterMode {
+        match (instruction / (10i64.pow(offset as u32 + 1))) % 10 {
+            0 => ParameterMode::Position,
+            1 => ParameterMode::Immediate,
+            2 => ParameterMode::Relative,
+            _ => ParameterMode::Unknown,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.status == Status::Finished
+    }
+
+    pub fn blocked(&self) -> bool {
+        self.status == Status::Blocked
+    }
+
+    pub fn r#continue(&mut self) {
+        if self.blocked() && self.inputs.len() > 0 {
+            self.set_status(Status::Running);
+        }
+        self.execute();
+    }
+
+    // Like r#continue, but gives up after max_ticks instructions, leaving the
+    // computer Running. Returns how many instructions ran.
+    pub fn run_for(&mut self, max_ticks:usize) -> usize {
+        if self.blocked() && self.inputs.len() > 0 {
+            self.set_status(Status::Running);
+        }
+        let mut ticks = 0;
+        while self.status == Status::Running && ticks < max_ticks {
+            self.tick();
+            ticks += 1;
+        }
+        ticks
+    }
+}